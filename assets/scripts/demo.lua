@@ -0,0 +1,22 @@
+local state = ...
+
+local demo = {}
+
+function demo.on_spawn()
+    print("Spawned: ", entity)
+end
+
+function demo.on_update(dt)
+    local tf = entity:get("Transform")
+    print("Transform is: ", tf)
+    print("Transform.translation is: ", tf.translation)
+    local translation = tf.translation
+    print("Transform.translation local is: ", tf.translation)
+    print("Transform.translation.x is: ", tf.translation.x)
+    local x = translation.x
+    print("Transform.translation.x local is: ", x)
+    local copy = translation:clone()
+    print("Translation copy is: ", copy)
+end
+
+return demo
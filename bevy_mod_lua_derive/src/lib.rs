@@ -0,0 +1,253 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro_crate::crate_name;
+use proc_macro_crate::FoundCrate;
+use quote::format_ident;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::*;
+
+/// Exposes a struct to Lua through a `Lua<Type>` userdata wrapping it.
+///
+/// Every named field becomes a Lua field, read and written as its own Lua equivalent, so field
+/// types must implement `LuaNewtype` themselves (floats, integers of up to 64 bits, `bool`,
+/// `String`, the math types or other proxies). `#[lua(skip)]` leaves a field out. The struct must
+/// be `Clone` and `Debug`, which `tostring` uses.
+///
+/// `#[lua(methods)]` on the struct adds the methods exported by [`macro@lua_methods`] on one of its
+/// `impl` blocks.
+#[proc_macro_derive(LuaProxy, attributes(lua))]
+pub fn derive_lua_proxy(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    lua_proxy(input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+/// Exports the functions of an `impl` block marked `#[lua(method)]` as Lua methods of the
+/// type's proxy, which must derive [`LuaProxy`] with `#[lua(methods)]`.
+///
+/// Arguments and return values are converted through `LuaNewtype`, like fields.
+#[proc_macro_attribute]
+pub fn lua_methods(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+
+    lua_impl(item).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+/// The package name of the scripting crate, as in its `Cargo.toml`.
+const LUA_PACKAGE: &str = "wasm_hosting_tests";
+
+/// The path of the scripting crate as seen from the crate using the macros.
+fn lua_crate() -> TokenStream2 {
+    // Found by package name, so renaming the package in `Cargo.toml` means renaming `LUA_PACKAGE`
+    let package = Ident::new(LUA_PACKAGE, Span::call_site());
+
+    match crate_name(LUA_PACKAGE) {
+        // Examples, tests and benches of the package are found as the package itself, but are
+        // crates of their own that reach it by name
+        Ok(FoundCrate::Itself) if std::env::var("CARGO_CRATE_NAME").as_deref() == Ok(LUA_PACKAGE) => quote!(crate),
+        Ok(FoundCrate::Itself) => quote!(::#package),
+        Ok(FoundCrate::Name(name)) => {
+            let name = Ident::new(&name, Span::call_site());
+            quote!(::#name)
+        },
+        Err(_) => quote!(::#package),
+    }
+}
+
+/// Whether `attrs` hold `#[lua(flag)]`. Other flags are rejected, as they'd be ignored.
+fn has_flag(attrs: &[Attribute], flag: &str, allowed: &[&str]) -> Result<bool> {
+    let mut found = false;
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("lua")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new(meta.span(), "expected #[lua(...)]")),
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if allowed.iter().any(|allowed| path.is_ident(allowed)) => {
+                    found |= path.is_ident(flag);
+                },
+                nested => return Err(Error::new(nested.span(), format!("expected one of: {}", allowed.join(", ")))),
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+fn lua_proxy(input: DeriveInput) -> Result<TokenStream2> {
+    let lua = lua_crate();
+    let mlua = quote!(#lua::mlua);
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "LuaProxy can't be derived for generic types"));
+    }
+
+    let fields: Vec<&Field> = match &input.data {
+        Data::Struct(DataStruct { fields: Fields::Named(fields), .. }) => fields.named.iter().collect(),
+        Data::Struct(DataStruct { fields: Fields::Unit, .. }) => Vec::new(),
+        _ => return Err(Error::new(input.ident.span(), "LuaProxy can only be derived for structs with named fields")),
+    };
+
+    let mut accessors = Vec::new();
+
+    for field in fields {
+        if has_flag(&field.attrs, "skip", &["skip"])? {
+            continue;
+        }
+
+        let (ident, ty) = (field.ident.as_ref().unwrap(), &field.ty);
+        let name = ident.to_string();
+
+        accessors.push(quote! {
+            fields.add_field_method_get(#name, |_, this| {
+                Ok(<#ty as #lua::LuaNewtype>::wrap(::std::clone::Clone::clone(&this.0.#ident)))
+            });
+            fields.add_field_method_set(#name, |_, this, value: <#ty as #lua::LuaNewtype>::Newtype| {
+                this.0.#ident = <#ty as #lua::LuaNewtype>::unwrap(value);
+                Ok(())
+            });
+        });
+    }
+
+    let ty = &input.ident;
+    let vis = &input.vis;
+    let proxy = format_ident!("Lua{}", ty);
+    let doc = format!("The Lua userdata of [`{}`].", ty);
+
+    // Left out for structs without fields, where `fields` would be unused
+    let fields = match accessors.is_empty() {
+        true => quote!(),
+        false => quote! {
+            fn add_fields<'lua, F: #mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+                #(#accessors)*
+            }
+        },
+    };
+
+    let methods = match has_flag(&input.attrs, "methods", &["methods"])? {
+        true => quote!(#ty::add_lua_methods(methods);),
+        false => quote!(),
+    };
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        #vis struct #proxy(pub #ty);
+
+        impl #mlua::UserData for #proxy {
+            #fields
+
+            fn add_methods<'lua, M: #mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+                methods.add_meta_method(#mlua::MetaMethod::ToString, |_, this, ()| {
+                    Ok(format!("{:?}", this.0))
+                });
+
+                #methods
+            }
+        }
+
+        impl #lua::LuaNewtype for #ty {
+            type Newtype = #proxy;
+
+            fn wrap(self) -> Self::Newtype {
+                #proxy(self)
+            }
+
+            fn unwrap(newtype: Self::Newtype) -> Self {
+                newtype.0
+            }
+
+            fn operand(value: &#mlua::Value) -> Option<Self> {
+                match value {
+                    #mlua::Value::UserData(userdata) => userdata.borrow::<#proxy>().ok().map(|newtype| newtype.0.clone()),
+                    _ => None,
+                }
+            }
+        }
+    })
+}
+
+fn lua_impl(mut item: ItemImpl) -> Result<TokenStream2> {
+    let lua = lua_crate();
+    let mlua = quote!(#lua::mlua);
+
+    let ty = &item.self_ty;
+    let mut exports = Vec::new();
+
+    for impl_item in &mut item.items {
+        let method = match impl_item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+
+        if !has_flag(&method.attrs, "method", &["method"])? {
+            continue;
+        }
+        method.attrs.retain(|attr| !attr.path.is_ident("lua"));
+
+        exports.push(export(&lua, &method.sig)?);
+    }
+
+    Ok(quote! {
+        #item
+
+        impl #ty {
+            #[doc(hidden)]
+            pub fn add_lua_methods<'lua, M>(methods: &mut M)
+            where
+                M: #mlua::UserDataMethods<'lua, <#ty as #lua::LuaNewtype>::Newtype>
+            {
+                #(#exports)*
+            }
+        }
+    })
+}
+
+/// Registers one `#[lua(method)]` function on `methods`.
+fn export(lua: &TokenStream2, sig: &Signature) -> Result<TokenStream2> {
+    let ident = &sig.ident;
+    let name = ident.to_string();
+
+    let (register, this) = match sig.receiver() {
+        Some(FnArg::Receiver(Receiver { reference: Some(_), mutability: Some(_), .. })) => (quote!(add_method_mut), quote!(this.0)),
+        Some(FnArg::Receiver(Receiver { reference: Some(_), .. })) => (quote!(add_method), quote!(this.0)),
+        Some(FnArg::Receiver(_)) => (quote!(add_method), quote!(::std::clone::Clone::clone(&this.0))),
+        _ => return Err(Error::new(sig.span(), "#[lua(method)] functions must take self, &self or &mut self")),
+    };
+
+    let mut args = Vec::new();
+    let mut arg_tys = Vec::new();
+
+    for (index, input) in sig.inputs.iter().skip(1).enumerate() {
+        match input {
+            FnArg::Typed(PatType { ty, .. }) => {
+                args.push(format_ident!("arg{}", index));
+                arg_tys.push(ty);
+            },
+            FnArg::Receiver(receiver) => return Err(Error::new(receiver.span(), "unexpected receiver")),
+        }
+    }
+
+    let call = quote!(#this.#ident(#(<#arg_tys as #lua::LuaNewtype>::unwrap(#args)),*));
+
+    let body = match &sig.output {
+        ReturnType::Default => quote! {
+            #call;
+            Ok(())
+        },
+        ReturnType::Type(_, ty) => quote! {
+            Ok(<#ty as #lua::LuaNewtype>::wrap(#call))
+        },
+    };
+
+    Ok(quote! {
+        methods.#register(#name, |_, this, (#(#args,)*): (#(<#arg_tys as #lua::LuaNewtype>::Newtype,)*)| {
+            #body
+        });
+    })
+}
@@ -0,0 +1,144 @@
+use bevy::asset::AssetLoader;
+use bevy::asset::HandleId;
+use bevy::asset::LoadContext;
+use bevy::asset::LoadedAsset;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use bevy::utils::HashMap;
+use mlua::prelude::*;
+
+use crate::BevyLua;
+use crate::LuaScriptError;
+
+/// A Lua source file loaded through the `AssetServer`.
+#[derive(Debug, TypeUuid)]
+#[uuid = "8e3b6c1f-2a8d-4f0e-9b57-3c4d1a6e2f90"]
+pub struct LuaScript {
+    /// The asset path the script was loaded from, used as its chunk name.
+    pub name: String,
+    pub source: String,
+}
+
+impl LuaScript {
+    /// The name passed to Lua when loading this script, so errors point at the file.
+    pub fn chunk_name(&self) -> String {
+        format!("@{}", self.name)
+    }
+}
+
+#[derive(Default)]
+pub struct LuaScriptLoader;
+
+impl AssetLoader for LuaScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let source = std::str::from_utf8(bytes)?.to_string();
+            let name = load_context.path().to_string_lossy().into_owned();
+
+            load_context.set_default_asset(LoadedAsset::new(LuaScript { name, source }));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lua"]
+    }
+}
+
+/// The callback tables and persistent state tables of every loaded [`LuaScript`].
+///
+/// A script is run once whenever it is (re)compiled and must return a table of callbacks
+/// (`on_spawn`, `on_update`, `on_despawn`). Its state table is passed to the chunk as the first
+/// vararg (`local state = ...`) and survives hot reloads, so scripts can keep data across edits.
+#[derive(Default)]
+pub struct LuaScriptCache {
+    modules: HashMap<HandleId, LuaRegistryKey>,
+    state: HashMap<HandleId, LuaRegistryKey>,
+}
+
+impl LuaScriptCache {
+    fn compile(&mut self, lua: &Lua, handle: HandleId, script: &LuaScript) -> LuaResult<()> {
+        let chunk = lua.load(&script.source).set_name(&script.chunk_name())?.into_function()?;
+
+        if !self.state.contains_key(&handle) {
+            let state = lua.create_registry_value(lua.create_table()?)?;
+            self.state.insert(handle, state);
+        }
+
+        let state: LuaTable = lua.registry_value(&self.state[&handle])?;
+        let module = match chunk.call::<_, LuaValue>(state)? {
+            LuaValue::Table(module) => lua.create_registry_value(module)?,
+            other => return Err(LuaError::RuntimeError(format!(
+                "Lua script {} must return a table of callbacks, but returned {}", script.name, other.type_name()
+            ))),
+        };
+
+        if let Some(old) = self.modules.insert(handle, module) {
+            lua.remove_registry_value(old)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, lua: &Lua, handle: HandleId) -> LuaResult<()> {
+        if let Some(module) = self.modules.remove(&handle) {
+            lua.remove_registry_value(module)?;
+        }
+
+        if let Some(state) = self.state.remove(&handle) {
+            lua.remove_registry_value(state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a script has been compiled.
+    pub fn contains(&self, handle: HandleId) -> bool {
+        self.modules.contains_key(&handle)
+    }
+
+    /// The callback table of a script, or `None` if it hasn't been compiled yet.
+    pub fn get<'lua>(&self, lua: &'lua Lua, handle: HandleId) -> LuaResult<Option<LuaTable<'lua>>> {
+        match self.modules.get(&handle) {
+            Some(module) => Ok(Some(lua.registry_value(module)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Recompiles scripts as they are loaded or modified on disk.
+///
+/// A script that fails to compile keeps running its previous version.
+pub(crate) fn lua_reload(
+    mut events: EventReader<AssetEvent<LuaScript>>,
+    scripts: Res<Assets<LuaScript>>,
+    lua: Res<BevyLua>,
+    mut cache: ResMut<LuaScriptCache>,
+    mut errors: EventWriter<LuaScriptError>,
+) {
+    let lua = lua.lock().expect("Failed to lock Lua mutex");
+
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if let Some(script) = scripts.get(handle) {
+                    if let Err(err) = cache.compile(&lua, handle.id, script) {
+                        error!("Failed to compile Lua script {}: {}", script.name, err);
+                        errors.send(LuaScriptError::new(handle.clone_weak(), None, &err));
+                    }
+                }
+            },
+            AssetEvent::Removed { handle } => {
+                if let Err(err) = cache.remove(&lua, handle.id) {
+                    error!("Failed to unload Lua script: {}", err);
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use std::any::TypeId;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+/// A component of an entity, or a resource when the entity is `None`.
+pub(crate) type BorrowKey = (Option<Entity>, TypeId);
+
+enum Borrow {
+    Shared(usize),
+    Exclusive,
+}
+
+/// Runtime borrow checking for the components and resources scripts reach through reflection.
+///
+/// Every read of a reflected value holds a shared borrow and every write an exclusive one for as
+/// long as the reference into the world is alive, so a write can never alias another reference.
+#[derive(Default)]
+pub(crate) struct BorrowTracker(Mutex<HashMap<BorrowKey, Borrow>>);
+
+impl BorrowTracker {
+    fn borrows(&self) -> MutexGuard<HashMap<BorrowKey, Borrow>> {
+        // The map is only changed by code that can't panic halfway, so a poisoned map is still consistent
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Borrows `key` for reading, or returns `None` if it is borrowed for writing.
+    pub fn shared(self: &Arc<Self>, key: BorrowKey) -> Option<BorrowGuard> {
+        let mut borrows = self.borrows();
+
+        match borrows.entry(key).or_insert(Borrow::Shared(0)) {
+            Borrow::Shared(count) => *count += 1,
+            Borrow::Exclusive => return None,
+        }
+
+        Some(BorrowGuard { tracker: self.clone(), key })
+    }
+
+    /// Borrows `key` for writing, or returns `None` if it is borrowed at all.
+    pub fn exclusive(self: &Arc<Self>, key: BorrowKey) -> Option<BorrowGuard> {
+        let mut borrows = self.borrows();
+
+        if borrows.contains_key(&key) {
+            return None;
+        }
+
+        borrows.insert(key, Borrow::Exclusive);
+
+        Some(BorrowGuard { tracker: self.clone(), key })
+    }
+}
+
+/// Releases a borrow taken from a [`BorrowTracker`] when dropped.
+pub(crate) struct BorrowGuard {
+    tracker: Arc<BorrowTracker>,
+    key: BorrowKey,
+}
+
+impl Drop for BorrowGuard {
+    fn drop(&mut self) {
+        let mut borrows = self.tracker.borrows();
+
+        let released = match borrows.get_mut(&self.key) {
+            Some(Borrow::Shared(count)) if *count > 1 => {
+                *count -= 1;
+                false
+            },
+            _ => true,
+        };
+
+        if released {
+            borrows.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(entity: u32) -> BorrowKey {
+        (Some(Entity::from_raw(entity)), TypeId::of::<u32>())
+    }
+
+    #[test]
+    fn shared_borrows_coexist() {
+        let tracker = Arc::new(BorrowTracker::default());
+
+        let _first = tracker.shared(key(1)).unwrap();
+        let _second = tracker.shared(key(1)).unwrap();
+    }
+
+    #[test]
+    fn shared_and_exclusive_exclude_each_other() {
+        let tracker = Arc::new(BorrowTracker::default());
+
+        let shared = tracker.shared(key(1)).unwrap();
+        assert!(tracker.exclusive(key(1)).is_none());
+        drop(shared);
+
+        let _exclusive = tracker.exclusive(key(1)).unwrap();
+        assert!(tracker.shared(key(1)).is_none());
+        assert!(tracker.exclusive(key(1)).is_none());
+    }
+
+    #[test]
+    fn borrows_of_other_keys_are_independent() {
+        let tracker = Arc::new(BorrowTracker::default());
+
+        let _exclusive = tracker.exclusive(key(1)).unwrap();
+        assert!(tracker.exclusive(key(2)).is_some());
+        assert!(tracker.shared((None, TypeId::of::<u32>())).is_some());
+    }
+
+    #[test]
+    fn dropping_a_guard_releases_only_its_own_borrow() {
+        let tracker = Arc::new(BorrowTracker::default());
+
+        let first = tracker.shared(key(1)).unwrap();
+        let second = tracker.shared(key(1)).unwrap();
+
+        drop(first);
+        assert!(tracker.exclusive(key(1)).is_none());
+
+        drop(second);
+        assert!(tracker.exclusive(key(1)).is_some());
+    }
+}
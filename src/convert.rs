@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use bevy::reflect::*;
+use mlua::prelude::*;
+use mlua::LuaSerdeExt;
+use mlua::Value;
+
+use std::sync::Arc;
+
+use crate::converter::LuaConverter;
+use crate::converter::LuaConverterRegistry;
+use crate::path;
+use crate::LuaCompRef;
+use crate::ReflectLuaEnum;
+use crate::ReferenceBase;
+
+/// Converts a Lua value into a reflected value shaped like `target`, so it can be assigned to it.
+///
+/// Tables become a `DynamicStruct`, `DynamicTupleStruct`, `DynamicTuple`, `DynamicList` or
+/// `DynamicMap` depending on the kind of `target`, recursing into its fields. References are
+/// cloned out of the world and must point to a value of the same type as `target`.
+pub(crate) fn lua_to_reflect<'lua>(lua: &'lua Lua, world: &World, value: Value<'lua>, target: &dyn Reflect) -> LuaResult<Box<dyn Reflect>> {
+    if let Value::UserData(userdata) = &value {
+        if let Ok(rvalue_ref) = userdata.borrow::<LuaCompRef>() {
+            return rvalue_ref.with_reflect(world, |rvalue| {
+                if rvalue.type_name() != target.type_name() {
+                    return Err(LuaError::RuntimeError(format!("Cannot assign {:?} of type {} to a value of type {}", *rvalue_ref, rvalue.type_name(), target.type_name())));
+                }
+
+                Ok(rvalue.clone_value())
+            });
+        }
+    }
+
+    match (target.reflect_ref(), value) {
+        (ReflectRef::Struct(target), Value::Table(table)) => {
+            let mut dynamic = DynamicStruct::default();
+            dynamic.set_name(target.type_name().to_string());
+
+            for pair in table.pairs::<LuaString, Value>() {
+                let (name, value) = pair?;
+                let name = name.to_str()?;
+
+                let field =
+                    target.field(name)
+                    .ok_or_else(|| LuaError::RuntimeError(format!("{} has no field {}", target.type_name(), name)))?;
+
+                dynamic.insert_boxed(name, lua_to_reflect(lua, world, value, field)?);
+            }
+
+            Ok(Box::new(dynamic))
+        },
+        (ReflectRef::TupleStruct(target), Value::Table(table)) => {
+            let mut dynamic = DynamicTupleStruct::default();
+            dynamic.set_name(target.type_name().to_string());
+
+            for (index, value) in table.sequence_values::<Value>().enumerate() {
+                let field =
+                    target.field(index)
+                    .ok_or_else(|| LuaError::RuntimeError(format!("{} has no field {}", target.type_name(), index + 1)))?;
+
+                dynamic.insert_boxed(lua_to_reflect(lua, world, value?, field)?);
+            }
+
+            Ok(Box::new(dynamic))
+        },
+        (ReflectRef::Tuple(target), Value::Table(table)) => {
+            let mut dynamic = DynamicTuple::default();
+
+            for (index, value) in table.sequence_values::<Value>().enumerate() {
+                let field =
+                    target.field(index)
+                    .ok_or_else(|| LuaError::RuntimeError(format!("{} has no field {}", target.type_name(), index + 1)))?;
+
+                dynamic.insert_boxed(lua_to_reflect(lua, world, value?, field)?);
+            }
+
+            Ok(Box::new(dynamic))
+        },
+        (ReflectRef::List(target), Value::Table(table)) => {
+            let mut dynamic = DynamicList::default();
+            dynamic.set_name(target.type_name().to_string());
+
+            for value in table.sequence_values::<Value>() {
+                dynamic.push_box(lua_to_element(lua, world, value?, target)?);
+            }
+
+            Ok(Box::new(dynamic))
+        },
+        (ReflectRef::Map(target), Value::Table(table)) => {
+            let mut dynamic = DynamicMap::default();
+            dynamic.set_name(target.type_name().to_string());
+
+            let (key_hint, value_hint) = match target.get_at(0) {
+                Some((key, value)) => (Some(key), Some(value)),
+                None => (None, None),
+            };
+
+            for pair in table.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                dynamic.insert_boxed(
+                    lua_to_reflect_like(lua, world, key, key_hint)?,
+                    lua_to_reflect_like(lua, world, value, value_hint)?,
+                );
+            }
+
+            Ok(Box::new(dynamic))
+        },
+        (ReflectRef::Value(target), value) => value_to_reflect(lua, world, value, target),
+        (target, value) => Err(LuaError::RuntimeError(format!("Cannot assign a {} to a value of type {}", value.type_name(), reflect_type_name(&target)))),
+    }
+}
+
+pub(crate) fn lua_to_reflect_like<'lua>(lua: &'lua Lua, world: &World, value: Value<'lua>, hint: Option<&dyn Reflect>) -> LuaResult<Box<dyn Reflect>> {
+    match hint {
+        Some(hint) => lua_to_reflect(lua, world, value, hint),
+        None => scalar_to_reflect(lua, world, value),
+    }
+}
+
+/// Converts a Lua value into a new element of `list`, shaped like its first element. The elements
+/// of an empty `Vec` are converted by the converter registered for its element type, or else by
+/// their Lua type.
+pub(crate) fn lua_to_element<'lua>(lua: &'lua Lua, world: &World, value: Value<'lua>, list: &dyn List) -> LuaResult<Box<dyn Reflect>> {
+    if let Some(first) = list.get(0) {
+        return lua_to_reflect(lua, world, value, first);
+    }
+
+    match element_converter(world, list) {
+        Some(converter) => converter.from_lua(lua, value),
+        None => scalar_to_reflect(lua, world, value),
+    }
+}
+
+/// The converter for the element type of a `Vec`, looked up by name in the `TypeRegistry`.
+fn element_converter<'w>(world: &'w World, list: &dyn List) -> Option<&'w LuaConverter> {
+    let name = path::element_type_name(list)?;
+    let type_id = world.get_resource::<TypeRegistryArc>()?.read().get_with_name(name)?.type_id();
+
+    world.get_resource::<LuaConverterRegistry>()?.get(type_id)
+}
+
+/// Converts a Lua value into a reflected value of exactly the type of `target`.
+fn value_to_reflect<'lua>(lua: &'lua Lua, world: &World, value: Value<'lua>, target: &dyn Reflect) -> LuaResult<Box<dyn Reflect>> {
+    if let Some(converter) = converter(world, target) {
+        return converter.from_lua(lua, value);
+    }
+
+    // Enums and other types reflected as a whole are built from their serialized form
+    if !matches!(value, Value::UserData(_)) {
+        if let Some(rvalue) = deserialize(world, value.clone(), target) {
+            return rvalue;
+        }
+    }
+
+    let rvalue = scalar_to_reflect(lua, world, value)?;
+
+    if rvalue.type_name() == target.type_name() {
+        Ok(rvalue)
+    } else {
+        Err(LuaError::RuntimeError(format!("Cannot assign a value of type {} to a value of type {}", rvalue.type_name(), target.type_name())))
+    }
+}
+
+/// The registered converter for values of the type of `value`.
+fn converter<'w>(world: &'w World, value: &dyn Reflect) -> Option<&'w LuaConverter> {
+    world.get_resource::<LuaConverterRegistry>()?.get_for(value)
+}
+
+/// Builds a value of `target`'s type with its registered `ReflectDeserialize`, from the form
+/// [`serialized_to_lua`] produces. Returns `None` if the type can't be deserialized.
+fn deserialize(world: &World, value: Value, target: &dyn Reflect) -> Option<LuaResult<Box<dyn Reflect>>> {
+    let registry = world.get_resource::<TypeRegistryArc>()?.read();
+    let deserialize = registry.get_type_data::<ReflectDeserialize>(target.any().type_id())?;
+
+    Some(
+        deserialize.deserialize(mlua::serde::Deserializer::new(value))
+        .map_err(|err| LuaError::RuntimeError(format!("Cannot assign to a value of type {}: {}", target.type_name(), err)))
+    )
+}
+
+/// Converts a Lua value into a reflected value by its Lua type alone.
+fn scalar_to_reflect<'lua>(lua: &'lua Lua, world: &World, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>> {
+    match value {
+        Value::Boolean(bool) => Ok(Box::new(bool)),
+        Value::Integer(int) => Ok(Box::new(int)),
+        Value::Number(float) => Ok(Box::new(float)),
+        Value::String(string) => Ok(Box::new(string.to_str()?.to_string())),
+        Value::UserData(userdata) => 
+            world.get_resource::<LuaConverterRegistry>()
+            .and_then(|registry| registry.from_userdata(lua, &userdata))
+            .ok_or_else(|| LuaError::RuntimeError("Cannot convert this userdata to a reflected value".to_string())),
+        value => Err(LuaError::RuntimeError(format!("Cannot convert a {} to a reflected value", value.type_name()))),
+    }
+}
+
+fn reflect_type_name<'r>(refl: &ReflectRef<'r>) -> &'r str {
+    match refl {
+        ReflectRef::Struct(refl) => refl.type_name(),
+        ReflectRef::TupleStruct(refl) => refl.type_name(),
+        ReflectRef::Tuple(refl) => refl.type_name(),
+        ReflectRef::List(refl) => refl.type_name(),
+        ReflectRef::Map(refl) => refl.type_name(),
+        ReflectRef::Value(refl) => refl.type_name(),
+    }
+}
+
+/// Converts a reflected value, read through `source`, into Lua values detached from the world.
+///
+/// Structs become tables keyed by field name, tuples and lists become sequences and maps become
+/// tables keyed by their converted keys. Other values are converted through their `Serialize`
+/// implementation, and values that have none are cloned into an owned, read-only reference, which
+/// can still be assigned back to a field of the same type.
+pub(crate) fn reflect_to_lua<'lua>(lua: &'lua Lua, world: &World, source: &LuaCompRef, value: &dyn Reflect) -> LuaResult<Value<'lua>> {
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            let table = lua.create_table()?;
+            for index in 0..value.field_len() {
+                if let (Some(name), Some(field)) = (value.name_at(index), value.field_at(index)) {
+                    table.set(name, reflect_to_lua(lua, world, source, field)?)?;
+                }
+            }
+            Ok(Value::Table(table))
+        },
+        ReflectRef::TupleStruct(value) => sequence_to_lua(lua, world, source, value.iter_fields()),
+        ReflectRef::Tuple(value) => sequence_to_lua(lua, world, source, value.iter_fields()),
+        ReflectRef::List(value) => sequence_to_lua(lua, world, source, value.iter()),
+        ReflectRef::Map(value) => {
+            let table = lua.create_table()?;
+            for (key, value) in value.iter() {
+                table.set(reflect_to_lua(lua, world, source, key)?, reflect_to_lua(lua, world, source, value)?)?;
+            }
+            Ok(Value::Table(table))
+        },
+        ReflectRef::Value(value) => match value_to_lua(lua, world, value).or_else(|| serialized_to_lua(lua, value)) {
+            Some(value) => value,
+            None => LuaCompRef {
+                base: ReferenceBase::Owned(Arc::from(value.clone_value())),
+                comp_id: value.any().type_id(),
+                path: Vec::new(),
+                ..source.clone()
+            }.to_lua(lua),
+        },
+    }
+}
+
+/// Converts a reflected value into its Lua equivalent, or returns `None` if it has none.
+pub(crate) fn value_to_lua<'lua>(lua: &'lua Lua, world: &World, value: &dyn Reflect) -> Option<LuaResult<Value<'lua>>> {
+    converter(world, value).map(|converter| converter.to_lua(lua, value))
+}
+
+/// Converts a reflected value through its `Serialize` implementation, if it has one. Enums
+/// become their variant's name, or a `{ Variant = payload }` table if the variant has fields.
+pub(crate) fn serialized_to_lua<'lua>(lua: &'lua Lua, value: &dyn Reflect) -> Option<LuaResult<Value<'lua>>> {
+    let serializable = value.serializable()?;
+
+    Some(lua.to_value(serializable.borrow()))
+}
+
+/// The variant name and payload (`nil` for unit variants) of an enum reflected as a value, or
+/// `None` if `value` isn't registered with [`ReflectLuaEnum`] or doesn't serialize like an enum.
+pub(crate) fn enum_to_lua<'lua>(lua: &'lua Lua, world: &World, value: &dyn Reflect) -> LuaResult<Option<(LuaString<'lua>, Value<'lua>)>> {
+    let is_enum =
+        world.get_resource::<TypeRegistryArc>()
+        .map_or(false, |registry| registry.read().get_type_data::<ReflectLuaEnum>(value.any().type_id()).is_some());
+
+    if !is_enum {
+        return Ok(None);
+    }
+
+    let serialized = match serialized_to_lua(lua, value) {
+        Some(serialized) => serialized?,
+        None => return Ok(None),
+    };
+
+    match serialized {
+        Value::String(variant) => Ok(Some((variant, Value::Nil))),
+        Value::Table(table) => {
+            let mut pairs = table.pairs::<Value, Value>();
+
+            match (pairs.next(), pairs.next()) {
+                (Some(pair), None) => match pair? {
+                    (Value::String(variant), payload) => Ok(Some((variant, payload))),
+                    _ => Ok(None),
+                },
+                _ => Ok(None),
+            }
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Reads the value a reference points to if it has a Lua equivalent, so references to numbers,
+/// strings or vectors can be used in arithmetic and comparisons. Other values are returned as is.
+pub(crate) fn operand<'lua>(lua: &'lua Lua, value: Value<'lua>) -> LuaResult<Value<'lua>> {
+    let reference = match &value {
+        Value::UserData(userdata) => match userdata.borrow::<LuaCompRef>() {
+            Ok(reference) => reference.clone(),
+            Err(_) => return Ok(value),
+        },
+        _ => return Ok(value),
+    };
+
+    let world = reference.world.read()?;
+
+    reference.with_reflect(&world, |leaf| {
+        value_to_lua(lua, &world, leaf)
+        .unwrap_or_else(|| Err(LuaError::RuntimeError(format!("{:?} of type {} can't be used as a value", reference, leaf.type_name()))))
+    })
+}
+
+/// [`operand`] for every argument of a call, so functions taking numbers or vectors can be passed
+/// references to them. Arguments without a Lua equivalent are passed as they are.
+pub(crate) fn operands<'lua>(lua: &'lua Lua, args: LuaMultiValue<'lua>) -> LuaMultiValue<'lua> {
+    args.into_iter()
+    .map(|arg| operand(lua, arg.clone()).unwrap_or(arg))
+    .collect()
+}
+
+fn sequence_to_lua<'lua, 'r>(lua: &'lua Lua, world: &World, source: &LuaCompRef, values: impl Iterator<Item = &'r dyn Reflect>) -> LuaResult<Value<'lua>> {
+    let values = 
+        values
+        .map(|value| reflect_to_lua(lua, world, source, value))
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    Ok(Value::Table(lua.create_sequence_from(values)?))
+}
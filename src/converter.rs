@@ -0,0 +1,291 @@
+use bevy::reflect::*;
+use bevy::utils::HashMap;
+use mlua::prelude::*;
+use mlua::Value;
+
+use std::any::TypeId;
+use std::convert::TryFrom;
+
+use crate::math;
+use crate::math::LuaNewtype;
+
+/// Converts a reflected value of one type into Lua.
+pub type ToLuaFn = for<'lua> fn(&'lua Lua, &dyn Reflect) -> LuaResult<Value<'lua>>;
+
+/// Converts a Lua value into a reflected value of one type.
+pub type FromLuaFn = for<'lua> fn(&'lua Lua, Value<'lua>) -> LuaResult<Box<dyn Reflect>>;
+
+/// Whether a userdata holds a value of one registered type.
+type UserDataProbe = for<'lua> fn(&'lua Lua, &LuaAnyUserData<'lua>) -> bool;
+
+/// How values of one reflected type cross between Rust and Lua.
+#[derive(Clone, Copy)]
+pub struct LuaConverter {
+    to_lua: ToLuaFn,
+    from_lua: FromLuaFn,
+}
+
+impl LuaConverter {
+    pub fn new(to_lua: ToLuaFn, from_lua: FromLuaFn) -> Self {
+        Self { to_lua, from_lua }
+    }
+
+    /// Converts `value`, which must be of the type this converter is registered for.
+    pub fn to_lua<'lua>(&self, lua: &'lua Lua, value: &dyn Reflect) -> LuaResult<Value<'lua>> {
+        (self.to_lua)(lua, value)
+    }
+
+    pub fn from_lua<'lua>(&self, lua: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>> {
+        (self.from_lua)(lua, value)
+    }
+}
+
+/// The reflected types that have a Lua equivalent, by `TypeId`.
+///
+/// Values of these types are copied into Lua when read and converted back when assigned, instead
+/// of being reached through references. Numbers, `bool`, `String` and the math types are
+/// registered by default; add more with [`LuaAppExt::register_lua_type`](crate::LuaAppExt::register_lua_type).
+#[derive(Clone)]
+pub struct LuaConverterRegistry {
+    converters: HashMap<TypeId, LuaConverter>,
+    /// The `TypeId` of the converter of each proxy, by the `TypeId` of its `Lua<Type>` newtype.
+    proxies: HashMap<TypeId, (UserDataProbe, TypeId)>,
+    /// The types registered with `register`, in order, whose `FromLua` may accept a userdata.
+    userdata: Vec<(UserDataProbe, TypeId)>,
+}
+
+impl Default for LuaConverterRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            converters: HashMap::default(),
+            proxies: HashMap::default(),
+            userdata: Vec::new(),
+        };
+
+        registry.register_with::<i8>(to_lua::<i8>, integer::<i8>);
+        registry.register_with::<i16>(to_lua::<i16>, integer::<i16>);
+        registry.register_with::<i32>(to_lua::<i32>, integer::<i32>);
+        registry.register_with::<i64>(to_lua::<i64>, integer::<i64>);
+        registry.register_with::<i128>(to_lua::<i128>, integer::<i128>);
+        registry.register_with::<isize>(to_lua::<isize>, integer::<isize>);
+        registry.register_with::<u8>(to_lua::<u8>, integer::<u8>);
+        registry.register_with::<u16>(to_lua::<u16>, integer::<u16>);
+        registry.register_with::<u32>(to_lua::<u32>, integer::<u32>);
+        registry.register_with::<u64>(to_lua::<u64>, integer::<u64>);
+        registry.register_with::<u128>(to_lua::<u128>, integer::<u128>);
+        registry.register_with::<usize>(to_lua::<usize>, integer::<usize>);
+        registry.register_with::<f32>(to_lua::<f32>, f32_from_lua);
+        registry.register_with::<f64>(to_lua::<f64>, f64_from_lua);
+        registry.register_with::<bool>(to_lua::<bool>, bool_from_lua);
+        registry.register::<String>();
+        math::register_converters(&mut registry);
+
+        registry
+    }
+}
+
+impl LuaConverterRegistry {
+    /// Converts values of `T` with its `ToLua` and `FromLua` implementations.
+    pub fn register<T>(&mut self)
+    where
+        T: Reflect + Clone + for<'lua> ToLua<'lua> + for<'lua> FromLua<'lua>
+    {
+        self.register_with::<T>(to_lua::<T>, from_lua::<T>);
+
+        if !self.userdata.iter().any(|(_, type_id)| *type_id == TypeId::of::<T>()) {
+            self.userdata.push((accepts::<T>, TypeId::of::<T>()));
+        }
+    }
+
+    /// Converts values of `T` to and from their proxy userdata, such as the one `LuaProxy` derives.
+    pub fn register_proxy<T>(&mut self)
+    where
+        T: LuaNewtype + Reflect + Clone,
+        T::Newtype: UserData + Send + 'static,
+    {
+        self.register_with::<T>(proxy_to_lua::<T>, proxy_from_lua::<T>);
+        self.proxies.insert(TypeId::of::<T::Newtype>(), (is_proxy::<T>, TypeId::of::<T>()));
+    }
+
+    /// Converts values of `T` with the given functions, replacing any previous converter.
+    pub fn register_with<T: Reflect>(&mut self, to_lua: ToLuaFn, from_lua: FromLuaFn) {
+        self.converters.insert(TypeId::of::<T>(), LuaConverter::new(to_lua, from_lua));
+    }
+
+    /// The converter for values of the type `type_id`, if one is registered.
+    pub fn get(&self, type_id: TypeId) -> Option<&LuaConverter> {
+        self.converters.get(&type_id)
+    }
+
+    /// The converter for values of the type of `value`, if one is registered.
+    pub fn get_for(&self, value: &dyn Reflect) -> Option<&LuaConverter> {
+        self.get(value.any().type_id())
+    }
+
+    /// The value held by a userdata, converted by the converter of its type: the proxy it is the
+    /// newtype of, or else the first type registered with `register` that accepts it.
+    pub(crate) fn from_userdata<'lua>(&self, lua: &'lua Lua, userdata: &LuaAnyUserData<'lua>) -> Option<Box<dyn Reflect>> {
+        // At most one proxy's newtype is the userdata's type
+        let (_, type_id) =
+            self.proxies.values()
+            .chain(self.userdata.iter())
+            .find(|(is, _)| is(lua, userdata))?;
+
+        self.get(*type_id)?.from_lua(lua, Value::UserData(userdata.clone())).ok()
+    }
+}
+
+fn to_lua<'lua, T>(lua: &'lua Lua, value: &dyn Reflect) -> LuaResult<Value<'lua>>
+where
+    T: Reflect + Clone + for<'l> ToLua<'l>
+{
+    value.downcast_ref::<T>()
+    .ok_or_else(|| LuaError::RuntimeError(format!("Expected a value of type {}, got {}", std::any::type_name::<T>(), value.type_name())))?
+    .clone()
+    .to_lua(lua)
+}
+
+fn from_lua<'lua, T>(lua: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>>
+where
+    T: Reflect + for<'l> FromLua<'l>
+{
+    let lua_type = value.type_name();
+
+    T::from_lua(value, lua)
+    .map(|value| Box::new(value) as Box<dyn Reflect>)
+    .map_err(|_| mismatch::<T>(lua_type))
+}
+
+pub(crate) fn proxy_to_lua<'lua, T>(lua: &'lua Lua, value: &dyn Reflect) -> LuaResult<Value<'lua>>
+where
+    T: LuaNewtype + Reflect + Clone,
+    T::Newtype: UserData + Send + 'static,
+{
+    match value.downcast_ref::<T>() {
+        Some(value) => Ok(Value::UserData(lua.create_userdata(value.clone().wrap())?)),
+        None => Err(LuaError::RuntimeError(format!("Expected a value of type {}, got {}", std::any::type_name::<T>(), value.type_name()))),
+    }
+}
+
+pub(crate) fn proxy_from_lua<'lua, T>(_: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>>
+where
+    T: LuaNewtype + Reflect,
+{
+    match T::operand(&value) {
+        Some(value) => Ok(Box::new(value)),
+        None => Err(mismatch::<T>(value.type_name())),
+    }
+}
+
+fn is_proxy<'lua, T>(_: &'lua Lua, userdata: &LuaAnyUserData<'lua>) -> bool
+where
+    T: LuaNewtype,
+    T::Newtype: UserData + 'static,
+{
+    userdata.is::<T::Newtype>()
+}
+
+fn accepts<'lua, T>(lua: &'lua Lua, userdata: &LuaAnyUserData<'lua>) -> bool
+where
+    T: for<'l> FromLua<'l>
+{
+    T::from_lua(Value::UserData(userdata.clone()), lua).is_ok()
+}
+
+fn number<T>(value: &Value) -> LuaResult<f64> {
+    match value {
+        Value::Integer(int) => Ok(*int as f64),
+        Value::Number(float) => Ok(*float),
+        _ => Err(mismatch::<T>(value.type_name())),
+    }
+}
+
+fn f32_from_lua<'lua>(_: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>> {
+    Ok(Box::new(number::<f32>(&value)? as f32))
+}
+
+fn f64_from_lua<'lua>(_: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>> {
+    Ok(Box::new(number::<f64>(&value)?))
+}
+
+/// Only accepts booleans, where `FromLua` would take any value by its truthiness.
+fn bool_from_lua<'lua>(_: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>> {
+    match value {
+        Value::Boolean(bool) => Ok(Box::new(bool)),
+        _ => Err(mismatch::<bool>(value.type_name())),
+    }
+}
+
+/// Converts a Lua number into an integer of type `I`, failing if it isn't whole or doesn't fit.
+fn integer<'lua, I>(_: &'lua Lua, value: Value<'lua>) -> LuaResult<Box<dyn Reflect>>
+where
+    I: Reflect + TryFrom<i128>
+{
+    // Casting to `i128` saturates outside [-2^127, 2^127) instead of failing
+    let i128_range = i128::MIN as f64..-(i128::MIN as f64);
+
+    let int = match value {
+        Value::Integer(int) => int as i128,
+        // LuaJIT has no integer subtype, so whole floats are integers too
+        Value::Number(float) if float.fract() == 0.0 && i128_range.contains(&float) => float as i128,
+        Value::Number(float) if float.fract() == 0.0 => return Err(LuaError::RuntimeError(format!(
+            "Cannot assign {} to a value of type {}: it is out of range", float, std::any::type_name::<I>()
+        ))),
+        Value::Number(float) => return Err(LuaError::RuntimeError(format!(
+            "Cannot assign {} to a value of type {}: it is not a whole number", float, std::any::type_name::<I>()
+        ))),
+        _ => return Err(mismatch::<I>(value.type_name())),
+    };
+
+    let int = I::try_from(int).map_err(|_| LuaError::RuntimeError(format!(
+        "Cannot assign {} to a value of type {}: it is out of range", int, std::any::type_name::<I>()
+    )))?;
+
+    Ok(Box::new(int))
+}
+
+fn mismatch<T>(lua_type: &str) -> LuaError {
+    LuaError::RuntimeError(format!("Cannot assign a {} to a value of type {}", lua_type, std::any::type_name::<T>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert<I: Reflect + TryFrom<i128> + Copy>(value: Value) -> LuaResult<I> {
+        let lua = Lua::new();
+        let int = integer::<I>(&lua, value)?;
+
+        Ok(*int.downcast_ref::<I>().unwrap())
+    }
+
+    #[test]
+    fn integer_accepts_whole_numbers() {
+        assert_eq!(convert::<i32>(Value::Integer(-7)).unwrap(), -7);
+        assert_eq!(convert::<u8>(Value::Number(255.0)).unwrap(), 255);
+        assert_eq!(convert::<i128>(Value::Number(2f64.powi(100))).unwrap(), 1 << 100);
+        assert_eq!(convert::<i128>(Value::Number(-(2f64.powi(127)))).unwrap(), i128::MIN);
+    }
+
+    #[test]
+    fn integer_rejects_fractions() {
+        assert!(convert::<i32>(Value::Number(2.5)).is_err());
+        assert!(convert::<i32>(Value::Number(f64::NAN)).is_err());
+        assert!(convert::<i32>(Value::Number(f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn integer_rejects_out_of_range() {
+        assert!(convert::<u8>(Value::Integer(256)).is_err());
+        assert!(convert::<u32>(Value::Number(-1.0)).is_err());
+        assert!(convert::<i64>(Value::Number(1e300)).is_err());
+        assert!(convert::<i128>(Value::Number(2f64.powi(127))).is_err());
+        assert!(convert::<i128>(Value::Number(-1e40)).is_err());
+    }
+
+    #[test]
+    fn integer_rejects_other_types() {
+        assert!(convert::<i32>(Value::Boolean(true)).is_err());
+        assert!(convert::<i32>(Value::Nil).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+use bevy::reflect::FromType;
+use bevy::reflect::TypeRegistry;
+use bevy::reflect::TypeRegistryArc;
+
+use std::any::TypeId;
+
+/// The default value scripts insert a component with, as in `entity:insert("Name")`.
+///
+/// Add `#[reflect(LuaDefault)]` (or `LuaDefault` to its `#[reflect_value(...)]`) to a reflected
+/// component implementing `Default`, and register it with `App::register_type`. `Transform` and
+/// `GlobalTransform` get it from [`LuaPlugin`](crate::LuaPlugin).
+#[derive(Clone)]
+pub struct ReflectLuaDefault {
+    default: fn() -> Box<dyn Reflect>,
+}
+
+impl ReflectLuaDefault {
+    pub fn default_value(&self) -> Box<dyn Reflect> {
+        (self.default)()
+    }
+}
+
+impl<T: Reflect + Default> FromType<T> for ReflectLuaDefault {
+    fn from_type() -> Self {
+        ReflectLuaDefault {
+            default: || Box::new(T::default()),
+        }
+    }
+}
+
+/// Gives bevy's own components scripts commonly insert their `ReflectLuaDefault`, once every
+/// plugin has registered its types.
+pub(crate) fn register_bevy_defaults(registry: Res<TypeRegistryArc>) {
+    let mut registry = registry.write();
+
+    add_default::<Transform>(&mut registry);
+    add_default::<GlobalTransform>(&mut registry);
+}
+
+fn add_default<T: Reflect + Default>(registry: &mut TypeRegistry) {
+    if let Some(registration) = registry.get_mut(TypeId::of::<T>()) {
+        registration.insert(<ReflectLuaDefault as FromType<T>>::from_type());
+    }
+}
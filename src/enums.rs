@@ -0,0 +1,16 @@
+use bevy::reflect::FromType;
+use bevy::reflect::Reflect;
+
+/// Marks an enum reflected as a value, so scripts read it through its serialized form: the
+/// variant name and fields, and `:is("Variant")`.
+///
+/// Add `LuaEnum` to its `#[reflect_value(...)]` next to `Serialize` and `Deserialize`, and register
+/// it with `App::register_type`. Other values are never treated as enums, whatever they serialize to.
+#[derive(Clone)]
+pub struct ReflectLuaEnum;
+
+impl<T: Reflect> FromType<T> for ReflectLuaEnum {
+    fn from_type() -> Self {
+        ReflectLuaEnum
+    }
+}
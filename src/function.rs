@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use mlua::prelude::*;
+use mlua::FromLuaMulti;
+use mlua::ToLuaMulti;
+
+use std::sync::Arc;
+
+use crate::LuaGlobalInit;
+use crate::LuaWorldRef;
+
+/// The registry key of the world reference host functions borrow the world through.
+pub(crate) const WORLD: &str = "bevy_mod_lua.world";
+
+/// Sets a value of a module table.
+type LuaFieldInit = Box<dyn Fn(&Lua, &LuaTable) -> LuaResult<()> + Send + Sync>;
+
+/// Globals added to the app before [`LuaPlugin`](crate::LuaPlugin), set once it creates the Lua state.
+#[derive(Default)]
+pub(crate) struct PendingLuaGlobals(pub Vec<LuaGlobalInit>);
+
+/// The contents of a global table built with
+/// [`LuaAppExt::add_lua_module`](crate::LuaAppExt::add_lua_module).
+#[derive(Default)]
+pub struct LuaModule {
+    fields: Vec<LuaFieldInit>,
+}
+
+impl LuaModule {
+    /// Adds a function with access to the world, like
+    /// [`LuaAppExt::add_lua_function`](crate::LuaAppExt::add_lua_function).
+    pub fn add_function<A, R, F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        A: for<'lua> FromLuaMulti<'lua>,
+        R: for<'lua> ToLuaMulti<'lua>,
+        F: Fn(&mut World, A) -> LuaResult<R> + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+        let function = Arc::new(function);
+
+        self.fields.push(Box::new(move |lua, table| table.set(name.as_str(), host_function(lua, function.clone())?)));
+        self
+    }
+
+    /// Adds a constant.
+    pub fn add_value<T>(&mut self, name: &str, value: T) -> &mut Self
+    where
+        T: for<'lua> ToLua<'lua> + Clone + Send + Sync + 'static
+    {
+        let name = name.to_string();
+
+        self.fields.push(Box::new(move |_, table| table.set(name.as_str(), value.clone())));
+        self
+    }
+
+    pub(crate) fn into_global(self, name: &str) -> LuaGlobalInit {
+        let name = name.to_string();
+
+        Box::new(move |lua| {
+            let table = lua.create_table()?;
+
+            for init in &self.fields {
+                init(lua, &table)?;
+            }
+
+            lua.globals().set(name.as_str(), table)
+        })
+    }
+}
+
+/// Sets a global function with access to the world.
+pub(crate) fn global_function<A, R, F>(name: &str, function: F) -> LuaGlobalInit
+where
+    A: for<'lua> FromLuaMulti<'lua>,
+    R: for<'lua> ToLuaMulti<'lua>,
+    F: Fn(&mut World, A) -> LuaResult<R> + Send + Sync + 'static,
+{
+    let name = name.to_string();
+    let function = Arc::new(function);
+
+    Box::new(move |lua| lua.globals().set(name.as_str(), host_function(lua, function.clone())?))
+}
+
+/// Wraps `function` in a Lua function that borrows the world exclusively for each call, which
+/// scripts can only do while they are being run.
+fn host_function<'lua, A, R, F>(lua: &'lua Lua, function: Arc<F>) -> LuaResult<LuaFunction<'lua>>
+where
+    A: for<'a> FromLuaMulti<'a>,
+    R: for<'a> ToLuaMulti<'a>,
+    F: Fn(&mut World, A) -> LuaResult<R> + Send + Sync + 'static,
+{
+    lua.create_function(move |lua, args: A| {
+        let world_ref: LuaWorldRef = lua.named_registry_value(WORLD)?;
+        let mut world = world_ref.write()?;
+
+        function(&mut world, args)
+    })
+}
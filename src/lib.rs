@@ -0,0 +1,1501 @@
+use std::any::TypeId;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
+use std::sync::RwLockWriteGuard;
+use std::sync::TryLockError;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::reflect::*;
+use bevy::utils::HashMap;
+use bevy::utils::HashSet;
+use mlua::prelude::*;
+use mlua::*;
+
+mod asset;
+mod borrow;
+mod convert;
+mod converter;
+mod default;
+mod enums;
+mod function;
+mod math;
+mod method;
+mod path;
+mod resource;
+mod script;
+mod system;
+
+pub use asset::LuaScript;
+pub use asset::LuaScriptCache;
+pub use asset::LuaScriptLoader;
+pub use bevy_mod_lua_derive::lua_methods;
+pub use bevy_mod_lua_derive::LuaProxy;
+pub use converter::FromLuaFn;
+pub use converter::LuaConverter;
+pub use converter::LuaConverterRegistry;
+pub use converter::ToLuaFn;
+pub use default::ReflectLuaDefault;
+pub use enums::ReflectLuaEnum;
+pub use function::LuaModule;
+pub use math::LuaNewtype;
+pub use method::LuaMethodRegistry;
+pub use mlua;
+pub use resource::ReflectResource;
+pub use script::LuaScriptError;
+pub use script::LuaScripts;
+pub use system::LuaSystem;
+
+use borrow::BorrowGuard;
+use borrow::BorrowKey;
+use borrow::BorrowTracker;
+use function::PendingLuaGlobals;
+use method::LuaMethod;
+use path::PathSegment;
+use script::LuaScriptInstances;
+use system::LuaAccess;
+
+/// The Lua state, shared so it can stay in the world while scripts run.
+///
+/// It is locked while the host runs scripts, so host functions can't lock it again.
+#[derive(Clone)]
+pub struct BevyLua(Arc<Mutex<Lua>>);
+
+impl Deref for BevyLua {
+    type Target = Mutex<Lua>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+type LuaGlobalInit = Box<dyn Fn(&Lua) -> Result<()> + Send + Sync>;
+
+/// Owns the [`BevyLua`] state and runs the Lua host every frame.
+pub struct LuaPlugin {
+    stage: CoreStage,
+    libs: StdLib,
+    globals: Vec<LuaGlobalInit>,
+}
+
+impl Default for LuaPlugin {
+    fn default() -> Self {
+        Self {
+            stage: CoreStage::Update,
+            libs: StdLib::ALL_SAFE,
+            globals: Vec::new(),
+        }
+    }
+}
+
+impl LuaPlugin {
+    /// The stage the host runs in; it always runs at the end of that stage.
+    pub fn in_stage(mut self, stage: CoreStage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// The standard libraries opened in the Lua state. Defaults to [`StdLib::ALL_SAFE`].
+    pub fn with_libs(mut self, libs: StdLib) -> Self {
+        self.libs = libs;
+        self
+    }
+
+    /// Sets a global before any script runs.
+    pub fn with_global<T>(mut self, name: &str, value: T) -> Self
+    where
+        T: for<'lua> ToLua<'lua> + Clone + Send + Sync + 'static
+    {
+        let name = name.to_string();
+        self.globals.push(Box::new(move |lua| lua.globals().set(name.as_str(), value.clone())));
+        self
+    }
+}
+
+impl Plugin for LuaPlugin {
+    fn build(&self, app: &mut App) {
+        let lua = Lua::new_with(self.libs, LuaOptions::new()).expect("Failed to create Lua state");
+        math::register(&lua).expect("Failed to register Lua math types");
+        register_operators(&lua).expect("Failed to register Lua operators");
+
+        for init in &self.globals {
+            init(&lua).expect("Failed to set Lua global");
+        }
+
+        // Functions and modules added to the app before this plugin
+        if let Some(pending) = app.world.remove_resource::<PendingLuaGlobals>() {
+            for init in pending.0 {
+                init(&lua).expect("Failed to set Lua global");
+            }
+        }
+
+        // Host functions borrow the world through the same reference as scripts
+        let world_ref = LuaWorldRef::default();
+        lua.set_named_registry_value(function::WORLD, world_ref.clone()).expect("Failed to register the Lua world reference");
+
+        app
+        .add_asset::<LuaScript>()
+        .init_asset_loader::<LuaScriptLoader>()
+        .insert_resource(BevyLua(Arc::new(Mutex::new(lua))))
+        .init_resource::<LuaScriptCache>()
+        .init_resource::<LuaScriptInstances>()
+        .insert_resource(world_ref)
+        .init_resource::<LuaConverterRegistry>()
+        .init_resource::<LuaMethodRegistry>()
+        .add_event::<LuaScriptError>()
+        .add_startup_system(default::register_bevy_defaults)
+        .add_system_to_stage(self.stage, asset::lua_reload)
+        .add_system_to_stage(self.stage, lua_host.exclusive_system().at_end());
+    }
+}
+
+/// Lua scripting setup on [`App`].
+pub trait LuaAppExt {
+    /// Lets scripts read and assign values of `T` as their Lua equivalent, using its `ToLua` and
+    /// `FromLua` implementations, instead of through references.
+    fn register_lua_type<T>(&mut self) -> &mut Self
+    where
+        T: Reflect + Clone + for<'lua> ToLua<'lua> + for<'lua> FromLua<'lua>;
+
+    /// Lets scripts read and assign values of `T` as its proxy userdata, such as the one
+    /// `#[derive(LuaProxy)]` generates.
+    fn register_lua_proxy<T>(&mut self) -> &mut Self
+    where
+        T: LuaNewtype + Reflect + Clone,
+        T::Newtype: UserData + Send + 'static;
+
+    /// Makes `method` callable as `name` on references to values of `T`, e.g.
+    /// `entity:get("Health"):damage(10)`, with the component or resource borrowed mutably.
+    fn add_lua_method<T, A, R, F>(&mut self, name: &str, method: F) -> &mut Self
+    where
+        T: Reflect,
+        A: for<'lua> FromLuaMulti<'lua>,
+        R: for<'lua> ToLuaMulti<'lua>,
+        F: Fn(&mut T, A) -> R + Send + Sync + 'static;
+
+    /// Sets a global function with exclusive access to the world while it runs, e.g.
+    /// `app.add_lua_function("spawn_enemy", |world, (x, y): (f32, f32)| ...)`.
+    fn add_lua_function<A, R, F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        A: for<'lua> FromLuaMulti<'lua>,
+        R: for<'lua> ToLuaMulti<'lua>,
+        F: Fn(&mut World, A) -> Result<R> + Send + Sync + 'static;
+
+    /// Sets a global table holding the functions and values `build` adds to it.
+    fn add_lua_module(&mut self, name: &str, build: impl FnOnce(&mut LuaModule)) -> &mut Self;
+}
+
+impl LuaAppExt for App {
+    fn register_lua_type<T>(&mut self) -> &mut Self
+    where
+        T: Reflect + Clone + for<'lua> ToLua<'lua> + for<'lua> FromLua<'lua>
+    {
+        self.world.get_resource_or_insert_with(LuaConverterRegistry::default).register::<T>();
+        self
+    }
+
+    fn register_lua_proxy<T>(&mut self) -> &mut Self
+    where
+        T: LuaNewtype + Reflect + Clone,
+        T::Newtype: UserData + Send + 'static,
+    {
+        self.world.get_resource_or_insert_with(LuaConverterRegistry::default).register_proxy::<T>();
+        self
+    }
+
+    fn add_lua_method<T, A, R, F>(&mut self, name: &str, method: F) -> &mut Self
+    where
+        T: Reflect,
+        A: for<'lua> FromLuaMulti<'lua>,
+        R: for<'lua> ToLuaMulti<'lua>,
+        F: Fn(&mut T, A) -> R + Send + Sync + 'static,
+    {
+        self.world.get_resource_or_insert_with(LuaMethodRegistry::default).add(name, method);
+        self
+    }
+
+    fn add_lua_function<A, R, F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        A: for<'lua> FromLuaMulti<'lua>,
+        R: for<'lua> ToLuaMulti<'lua>,
+        F: Fn(&mut World, A) -> Result<R> + Send + Sync + 'static,
+    {
+        add_lua_global(self, function::global_function(name, function))
+    }
+
+    fn add_lua_module(&mut self, name: &str, build: impl FnOnce(&mut LuaModule)) -> &mut Self {
+        let mut module = LuaModule::default();
+        build(&mut module);
+
+        add_lua_global(self, module.into_global(name))
+    }
+}
+
+/// Sets a global right away if the Lua state exists, or once [`LuaPlugin`] creates it.
+fn add_lua_global(app: &mut App, init: LuaGlobalInit) -> &mut App {
+    match app.world.get_resource::<BevyLua>() {
+        Some(lua) => init(&lua.lock().expect("Failed to lock Lua mutex")).expect("Failed to set Lua global"),
+        None => app.world.get_resource_or_insert_with(PendingLuaGlobals::default).0.push(init),
+    }
+
+    app
+}
+
+struct LuaEntity {
+    entity: Entity,
+    world: LuaWorldRef
+}
+
+impl LuaEntity {
+    /// Errors if the entity was despawned, including when its id has since been reused.
+    fn check(&self, world: &World) -> Result<()> {
+        match world.get_entity(self.entity) {
+            Some(_) => Ok(()),
+            None => Err(despawned(self.entity)),
+        }
+    }
+}
+
+impl UserData for LuaEntity {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("despawn", |_, this, _: ()| {
+            let mut world = this.world.write()?;
+
+            this.check(&world)?;
+
+            Ok(world.despawn(this.entity))
+        });
+
+        methods.add_method("alive", |_, this, _: ()| {
+            Ok(this.world.read()?.get_entity(this.entity).is_some())
+        });
+        
+        methods.add_method_mut("get", |_, this, comp_name: String| {
+            let world = this.world.read()?;
+
+            this.check(&world)?;
+
+            let comp = 
+                ComponentType::named(&this.world, &world, comp_name.to_str()?)?
+                .filter(|ty| ty.comp.reflect_component(&world, this.entity).is_some())
+                .map(|ty| ty.on(&this.world, this.entity));
+
+            Ok(comp)
+        });
+
+        methods.add_method("has", |_, this, comp_name: String| {
+            let world = this.world.read()?;
+
+            this.check(&world)?;
+
+            let ty = ComponentType::registered(&this.world, &world, comp_name.to_str()?)?;
+
+            Ok(ty.comp.reflect_component(&world, this.entity).is_some())
+        });
+
+        methods.add_method("insert", |lua, this, (comp_name, fields): (String, Option<Table>)| {
+            let comp = {
+                let mut world = this.world.write()?;
+
+                this.check(&world)?;
+
+                let ty = ComponentType::registered(&this.world, &world, comp_name.to_str()?)?;
+                ty.insert_default(&mut world, this.entity)?;
+
+                ty.on(&this.world, this.entity)
+            };
+
+            if let Some(fields) = fields {
+                for pair in fields.pairs::<String, Value>() {
+                    let (key, value) = pair?;
+                    comp.set(lua, PathSegment::Field(key.to_str()?.to_string()), value)?;
+                }
+            }
+
+            Ok(comp)
+        });
+
+        methods.add_method("remove", |_, this, comp_name: String| {
+            let mut world = this.world.write()?;
+
+            this.check(&world)?;
+
+            let ty = ComponentType::registered(&this.world, &world, comp_name.to_str()?)?;
+
+            if ty.comp.reflect_component(&world, this.entity).is_none() {
+                return Ok(false);
+            }
+
+            ty.comp.remove_component(&mut world, this.entity);
+
+            Ok(true)
+        });
+    }
+}
+
+/// A component type resolved by name through the `TypeRegistry`.
+#[derive(Clone)]
+struct ComponentType {
+    comp: ReflectComponent,
+    comp_id: TypeId,
+    comp_name: &'static str,
+}
+
+/// Looks up a registered type by its short or full type name, along with its type data `D`.
+fn registered_type<D: TypeData + Clone>(world: &World, name: &str) -> Option<(D, TypeId, &'static str)> {
+    let registry = world.get_resource::<TypeRegistryArc>()?.read();
+
+    let reg = registry.get_with_short_name(name).or(registry.get_with_name(name))?;
+
+    Some((reg.data::<D>()?.to_owned(), reg.type_id(), reg.name()))
+}
+
+impl ComponentType {
+    /// Resolves a component by name, failing if `world_ref` may not read it: a Lua system can't
+    /// even check for components it didn't declare.
+    fn named(world_ref: &LuaWorldRef, world: &World, comp_name: &str) -> Result<Option<Self>> {
+        let (comp, comp_id, comp_name) = match registered_type(world, comp_name) {
+            Some(registered) => registered,
+            None => return Ok(None),
+        };
+
+        world_ref.check_access(comp_id, comp_name, false)?;
+
+        Ok(Some(ComponentType { comp, comp_id, comp_name }))
+    }
+
+    fn registered(world_ref: &LuaWorldRef, world: &World, comp_name: &str) -> Result<Self> {
+        Self::named(world_ref, world, comp_name)?
+        .ok_or(Error::RuntimeError(format!("{} is not a registered component", comp_name)))
+    }
+
+    /// Inserts the component's [`ReflectLuaDefault`] value on `entity`, replacing any existing one.
+    fn insert_default(&self, world: &mut World, entity: Entity) -> Result<()> {
+        let default = {
+            let registry = 
+                world.get_resource::<TypeRegistryArc>()
+                .ok_or_else(|| Error::RuntimeError("The type registry is missing".to_string()))?
+                .read();
+
+            registry.get_type_data::<ReflectLuaDefault>(self.comp_id)
+            .map(ReflectLuaDefault::default_value)
+            .ok_or_else(|| Error::RuntimeError(format!("Cannot insert {}: it has no default value registered with #[reflect(LuaDefault)]", self.comp_name)))?
+        };
+
+        // The default is of the component's exact type, so applying it can't fail
+        self.comp.add_component(world, entity, &*default);
+
+        Ok(())
+    }
+
+    fn on(&self, world: &LuaWorldRef, entity: Entity) -> LuaCompRef {
+        LuaCompRef {
+            world: world.clone(),
+            base: ReferenceBase::Component(entity, self.comp.clone()),
+            comp_id: self.comp_id,
+            comp_name: self.comp_name,
+            path: Vec::new(),
+        }
+    }
+}
+
+/// The component names of a `world:query` table and its `with`/`without` filters, which Lua
+/// systems must all have declared as read.
+struct LuaQuery {
+    fetch: Vec<ComponentType>,
+    with: Vec<ComponentType>,
+    without: Vec<ComponentType>,
+}
+
+impl LuaQuery {
+    fn parse(world_ref: &LuaWorldRef, world: &World, query: Table) -> Result<Self> {
+        let names = |table: Table| -> Result<Vec<ComponentType>> {
+            table.sequence_values::<String>()
+            .map(|name| ComponentType::registered(world_ref, world, name?.to_str()?))
+            .collect()
+        };
+
+        Ok(LuaQuery {
+            fetch: names(query.clone())?,
+            with: query.get::<_, Option<Table>>("with")?.map(names).transpose()?.unwrap_or_default(),
+            without: query.get::<_, Option<Table>>("without")?.map(names).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// Every entity currently matching the query.
+    fn entities(&self, world: &World) -> Vec<Entity> {
+        let components = world.components();
+
+        // A component that was never added to the world can't be on any entity
+        let required: Option<Vec<_>> = 
+            self.fetch.iter()
+            .chain(self.with.iter())
+            .map(|ty| components.get_id(ty.comp_id))
+            .collect();
+        let required = match required {
+            Some(required) => required,
+            None => return Vec::new(),
+        };
+
+        let excluded: Vec<_> = 
+            self.without.iter()
+            .filter_map(|ty| components.get_id(ty.comp_id))
+            .collect();
+
+        world.archetypes().iter()
+        .filter(|archetype| required.iter().all(|id| archetype.contains(*id)))
+        .filter(|archetype| !excluded.iter().any(|id| archetype.contains(*id)))
+        .flat_map(|archetype| archetype.entities().iter().copied())
+        .collect()
+    }
+
+    /// Whether `entity` still matches, as scripts may change entities while iterating.
+    fn matches(&self, world: &World, entity: Entity) -> bool {
+        world.get_entity(entity).is_some()
+        && self.fetch.iter().chain(self.with.iter()).all(|ty| ty.comp.reflect_component(world, entity).is_some())
+        && !self.without.iter().any(|ty| ty.comp.reflect_component(world, entity).is_some())
+    }
+}
+
+/// A pointer to the world lent to scripts by [`LuaWorldRef::scope`] or [`LuaWorldRef::scope_shared`].
+struct WorldPtr(*mut World);
+
+// SAFETY: The pointer is only dereferenced through the guards of the `LuaWorldRef` lock, and only
+// while the scope lending it holds the borrow of the world it points to
+unsafe impl Send for WorldPtr {}
+unsafe impl Sync for WorldPtr {}
+
+/// Scripts' access to the world, which is only lent to them inside [`LuaWorldRef::scope`].
+///
+/// One `LuaWorldRef` lives in the world as a resource and every handle given to scripts shares it,
+/// so handles that scripts keep in globals work again the next frame, and fail with a Lua error
+/// rather than dangling when used while the world isn't lent out. Each [`LuaSystem`] has its own,
+/// limited to the access it declared.
+#[derive(Clone, Default)]
+struct LuaWorldRef {
+    world: Arc<RwLock<Option<WorldPtr>>>,
+    borrows: Arc<BorrowTracker>,
+    access: Option<Arc<LuaAccess>>,
+}
+
+struct WorldReadGuard<'a> {
+    _guard: RwLockReadGuard<'a, Option<WorldPtr>>,
+    world: *const World,
+}
+
+impl Deref for WorldReadGuard<'_> {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        // SAFETY: The scope lending the world outlives the guard, and the lock rules out a writer
+        unsafe { &*self.world }
+    }
+}
+
+struct WorldWriteGuard<'a> {
+    _guard: RwLockWriteGuard<'a, Option<WorldPtr>>,
+    world: *mut World,
+}
+
+impl Deref for WorldWriteGuard<'_> {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        // SAFETY: See `deref_mut`
+        unsafe { &*self.world }
+    }
+}
+
+impl DerefMut for WorldWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut World {
+        // SAFETY: The scope lending the world outlives the guard, and the lock rules out any other access
+        unsafe { &mut *self.world }
+    }
+}
+
+fn world_lock_error<T>(err: TryLockError<T>) -> Error {
+    match err {
+        // Lua runs on one thread, so the lock can only be taken already by a caller further up the stack
+        TryLockError::WouldBlock => Error::RuntimeError("The world is already borrowed by another operation".to_string()),
+        TryLockError::Poisoned(_) => Error::RuntimeError("The world lock was poisoned by an earlier panic".to_string()),
+    }
+}
+
+fn world_expired() -> Error {
+    Error::RuntimeError("The world can only be accessed while scripts are being run".to_string())
+}
+
+fn despawned(entity: Entity) -> Error {
+    Error::RuntimeError(format!("entity {:?} was despawned", entity))
+}
+
+impl LuaWorldRef {
+    /// Lends `world` to every handle sharing this reference for the duration of `f`.
+    fn scope<R>(&self, world: &mut World, f: impl FnOnce() -> R) -> R {
+        self.lend_during(WorldPtr(world), f)
+    }
+
+    /// Lends `world` to a Lua system for the duration of `f`, with only shared access to the world
+    /// itself.
+    ///
+    /// # Safety
+    /// This reference must be limited to an access that the caller holds on `world`, as this
+    /// reference can still write to the components and resources it permits.
+    unsafe fn scope_shared<R>(&self, world: &World, f: impl FnOnce() -> R) -> R {
+        debug_assert!(self.access.is_some());
+
+        // `write` refuses to hand out the world when an access is set, so it is never mutated as a whole
+        self.lend_during(WorldPtr(world as *const World as *mut World), f)
+    }
+
+    fn lend_during<R>(&self, world: WorldPtr, f: impl FnOnce() -> R) -> R {
+        struct Release<'a>(&'a LuaWorldRef);
+
+        impl Drop for Release<'_> {
+            // Runs even if `f` panics, so the pointer never outlives the borrow it came from
+            fn drop(&mut self) {
+                self.0.lend(None);
+            }
+        }
+
+        self.lend(Some(world));
+        let _release = Release(self);
+
+        f()
+    }
+
+    fn lend(&self, world: Option<WorldPtr>) {
+        match self.world.write() {
+            Ok(mut ptr) => *ptr = world,
+            Err(poisoned) => *poisoned.into_inner() = world,
+        }
+    }
+
+    fn read(&self) -> Result<WorldReadGuard<'_>> {
+        let guard = self.world.try_read().map_err(world_lock_error)?;
+        let world = guard.as_ref().ok_or_else(world_expired)?.0 as *const World;
+
+        Ok(WorldReadGuard { _guard: guard, world })
+    }
+
+    fn write(&self) -> Result<WorldWriteGuard<'_>> {
+        if self.access.is_some() {
+            return Err(Error::RuntimeError("Lua systems can't spawn, despawn, insert or remove components".to_string()));
+        }
+
+        let guard = self.world.try_write().map_err(world_lock_error)?;
+        let world = guard.as_ref().ok_or_else(world_expired)?.0;
+
+        Ok(WorldWriteGuard { _guard: guard, world })
+    }
+
+    /// Fails if this reference belongs to a Lua system that didn't declare the access to `type_id`.
+    fn check_access(&self, type_id: TypeId, type_name: &str, write: bool) -> Result<()> {
+        match &self.access {
+            Some(access) if !access.permits(type_id, write) => Err(Error::RuntimeError(format!(
+                "This Lua system didn't declare that it {} {}", if write { "writes" } else { "reads" }, type_name
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// References a registered resource by name, or `nil` if it isn't in the world.
+    fn resource(&self, res_name: &str, mutable: bool) -> Result<Option<LuaCompRef>> {
+        let world = self.read()?;
+
+        let (res, res_id, res_name) = 
+            registered_type::<ReflectResource>(&world, res_name)
+            .ok_or(Error::RuntimeError(format!("{} is not a registered resource", res_name)))?;
+
+        self.check_access(res_id, res_name, mutable)?;
+
+        if res.reflect_resource(&world).is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(LuaCompRef {
+            world: self.clone(),
+            base: ReferenceBase::Resource { res, mutable },
+            comp_id: res_id,
+            comp_name: res_name,
+            path: Vec::new(),
+        }))
+    }
+}
+
+impl UserData for LuaWorldRef {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("spawn", |_, this, ()| {
+            let entity = this.write()?.spawn().id();
+
+            Ok(LuaEntity { entity, world: this.clone() })
+        });
+
+        methods.add_method("resource", |_, this, res_name: String| {
+            this.resource(res_name.to_str()?, false)
+        });
+
+        methods.add_method("resource_mut", |_, this, res_name: String| {
+            this.resource(res_name.to_str()?, true)
+        });
+
+        // for entity, tf, vel in world:query({"Transform", "Velocity", with = {"Player"}, without = {"Dead"}}) do
+        methods.add_method("query", |lua, this, query: Table| {
+            let (query, entities) = {
+                let world = this.read()?;
+
+                let query = LuaQuery::parse(this, &world, query)?;
+                let entities = query.entities(&world);
+
+                (query, entities)
+            };
+
+            let world_ref = this.clone();
+            let mut entities = entities.into_iter();
+
+            lua.create_function_mut(move |lua, ()| {
+                let world = world_ref.read()?;
+
+                for entity in entities.by_ref() {
+                    if !query.matches(&world, entity) {
+                        continue;
+                    }
+
+                    let mut values = vec![LuaEntity { entity, world: world_ref.clone() }.to_lua(lua)?];
+                    for ty in &query.fetch {
+                        values.push(ty.on(&world_ref, entity).to_lua(lua)?);
+                    }
+
+                    return Ok(MultiValue::from_vec(values));
+                }
+
+                Ok(MultiValue::new())
+            })
+        });
+    }
+}
+
+/// The value a [`LuaCompRef`] points into.
+#[derive(Clone)]
+enum ReferenceBase {
+    Component(Entity, ReflectComponent),
+    Resource { res: ReflectResource, mutable: bool },
+    /// A read-only value detached from the world by `clone()`.
+    Owned(Arc<dyn Reflect>),
+}
+
+#[derive(Clone)]
+struct LuaCompRef {
+    world: LuaWorldRef,
+    base: ReferenceBase,
+    comp_id: TypeId,
+    comp_name: &'static str,
+    path: Vec<PathSegment>,
+}
+
+impl LuaCompRef {
+    /// The value this reference is rooted at, ignoring its path.
+    fn reflect_base<'a>(&'a self, world: &'a World) -> Result<&'a dyn Reflect> {
+        match &self.base {
+            ReferenceBase::Component(entity, _) if world.get_entity(*entity).is_none() => Err(despawned(*entity)),
+            ReferenceBase::Component(entity, comp) => 
+                comp.reflect_component(world, *entity)
+                .ok_or(Error::RuntimeError(format!("Component {} is not present on entity {:?}", self.comp_name, entity))),
+            ReferenceBase::Resource { res, .. } => 
+                res.reflect_resource(world)
+                .ok_or(Error::RuntimeError(format!("Resource {} does not exist", self.comp_name))),
+            ReferenceBase::Owned(value) => Ok(&**value),
+        }
+    }
+
+    /// # Safety
+    /// Nothing else may access the same component or resource while the returned reference is alive.
+    unsafe fn reflect_base_unchecked_mut<'w>(&self, world: &'w World) -> Result<&'w mut dyn Reflect> {
+        match &self.base {
+            ReferenceBase::Component(entity, _) if world.get_entity(*entity).is_none() => Err(despawned(*entity)),
+            ReferenceBase::Component(entity, comp) => 
+                comp.reflect_component_unchecked_mut(world, *entity)
+                .map(|comp| comp.into_inner())
+                .ok_or(Error::RuntimeError(format!("Component {} is not present on entity {:?}", self.comp_name, entity))),
+            ReferenceBase::Resource { mutable: false, .. } => 
+                Err(Error::RuntimeError(format!("Resource {} is read-only; use world:resource_mut to modify it", self.comp_name))),
+            ReferenceBase::Resource { res, mutable: true } => 
+                res.reflect_resource_unchecked_mut(world)
+                .ok_or(Error::RuntimeError(format!("Resource {} does not exist", self.comp_name))),
+            ReferenceBase::Owned(_) => 
+                Err(Error::RuntimeError(format!("{:?} is a cloned value and can't be modified; assign it to a field instead", self))),
+        }
+    }
+
+    /// The component or resource this reference borrows, or `None` for owned values, which are never written.
+    fn borrow_key(&self) -> Option<BorrowKey> {
+        match &self.base {
+            ReferenceBase::Component(entity, _) => Some((Some(*entity), self.comp_id)),
+            ReferenceBase::Resource { .. } => Some((None, self.comp_id)),
+            ReferenceBase::Owned(_) => None,
+        }
+    }
+
+    /// Fails if this reference leads outside the access of the Lua system using it.
+    fn check_access(&self, write: bool) -> Result<()> {
+        self.world.check_access(self.comp_id, self.comp_name, write)
+    }
+
+    fn borrow(&self) -> Result<Option<BorrowGuard>> {
+        if self.borrow_key().is_some() {
+            self.check_access(false)?;
+        }
+
+        match self.borrow_key() {
+            Some(key) => 
+                self.world.borrows.shared(key)
+                .map(Some)
+                .ok_or_else(|| Error::RuntimeError(format!("Cannot read {:?} while it is being written to", self))),
+            None => Ok(None),
+        }
+    }
+
+    fn borrow_mut(&self) -> Result<Option<BorrowGuard>> {
+        if self.borrow_key().is_some() {
+            self.check_access(true)?;
+        }
+
+        match self.borrow_key() {
+            Some(key) => 
+                self.world.borrows.exclusive(key)
+                .map(Some)
+                .ok_or_else(|| Error::RuntimeError(format!("Cannot write to {:?} while it is being accessed", self))),
+            None => Ok(None),
+        }
+    }
+
+    /// The value this reference points to.
+    fn reflect<'a>(&'a self, world: &'a World) -> Result<&'a dyn Reflect> {
+        path::walk(self.reflect_base(world)?, &self.path).ok_or_else(|| self.invalid_path())
+    }
+
+    /// The list this reference points to.
+    ///
+    /// # Safety
+    /// Same as [`LuaCompRef::reflect_base_unchecked_mut`].
+    unsafe fn list_unchecked_mut<'w>(&self, world: &'w World) -> Result<&'w mut dyn List> {
+        let value = 
+            path::walk_mut(self.reflect_base_unchecked_mut(world)?, &self.path)
+            .ok_or_else(|| self.invalid_path())?;
+
+        match value.reflect_mut() {
+            ReflectMut::List(list) => Ok(list),
+            _ => Err(Error::RuntimeError(format!("{:?} is not a list", self))),
+        }
+    }
+
+    fn invalid_path(&self) -> Error {
+        Error::RuntimeError(format!("The path {:?} is invalid", self))
+    }
+
+    /// A reference to a part of the value this reference points to.
+    fn child(&self, segment: PathSegment) -> LuaCompRef {
+        let mut child = self.clone();
+        child.path.push(segment);
+        child
+    }
+
+    /// Resolves a Lua key into the part of the value this reference points to that it selects.
+    /// List and tuple indices are 1-based, and map keys are converted to the map's key type.
+    fn segment<'lua>(&self, lua: &'lua Lua, world: &World, key: Value<'lua>) -> Result<PathSegment> {
+        let _borrow = self.borrow()?;
+
+        match (self.reflect(world)?.reflect_ref(), key) {
+            (ReflectRef::Map(map), key) => {
+                let label = key_label(&key)?;
+                let hint = map.get_at(0).map(|(key, _)| key);
+
+                Ok(PathSegment::Key(Arc::from(convert::lua_to_reflect_like(lua, world, key, hint)?), label))
+            },
+            (ReflectRef::List(_) | ReflectRef::Tuple(_) | ReflectRef::TupleStruct(_), key) => Ok(PathSegment::Index(lua_index(&key)?)),
+            (_, Value::String(name)) => Ok(PathSegment::Field(name.to_str()?.to_string())),
+            (_, key) => Err(Error::RuntimeError(format!("Cannot index {:?} with a {}", self, key.type_name()))),
+        }
+    }
+
+    /// The Lua equivalent of the value this reference points to, if it is reflected as a whole and
+    /// has one, like a vector.
+    fn leaf_value<'lua>(&self, lua: &'lua Lua, world: &World) -> Result<Option<Value<'lua>>> {
+        self.with_reflect(world, |value| match value.reflect_ref() {
+            ReflectRef::Value(_) => convert::value_to_lua(lua, world, value).transpose(),
+            _ => Ok(None),
+        })
+    }
+
+    /// This reference as a Lua value. Numbers, strings and booleans are read out, since Lua only
+    /// compares and tests them as plain values; anything else stays a reference.
+    fn into_field<'lua>(self, lua: &'lua Lua, world: &World) -> Result<Value<'lua>> {
+        match self.leaf_value(lua, world)? {
+            Some(leaf @ (Value::Integer(_) | Value::Number(_) | Value::String(_) | Value::Boolean(_))) => Ok(leaf),
+            _ => self.to_lua(lua),
+        }
+    }
+
+    /// The number of elements of the list, map or tuple this reference points to.
+    fn len(&self, world: &World) -> Result<Option<usize>> {
+        self.with_reflect(world, |value| Ok(match value.reflect_ref() {
+            ReflectRef::List(list) => Some(list.len()),
+            ReflectRef::Map(map) => Some(map.len()),
+            ReflectRef::Tuple(tuple) => Some(tuple.field_len()),
+            ReflectRef::TupleStruct(tuple) => Some(tuple.field_len()),
+            _ => None,
+        }))
+    }
+
+    /// Every part of the value this reference points to, in order: struct fields, tuple and list
+    /// elements or map entries.
+    fn entries(&self, world: &World) -> Result<Vec<PathSegment>> {
+        self.with_reflect(world, |value| match value.reflect_ref() {
+            ReflectRef::Struct(value) => Ok(
+                (0..value.field_len())
+                .filter_map(|index| value.name_at(index))
+                .map(|name| PathSegment::Field(name.to_string()))
+                .collect()
+            ),
+            ReflectRef::TupleStruct(value) => Ok((0..value.field_len()).map(PathSegment::Index).collect()),
+            ReflectRef::Tuple(value) => Ok((0..value.field_len()).map(PathSegment::Index).collect()),
+            ReflectRef::List(value) => Ok((0..value.len()).map(PathSegment::Index).collect()),
+            ReflectRef::Map(value) => 
+                value.iter()
+                .map(|(key, _)| Ok(PathSegment::Key(Arc::from(key.clone_value()), format!("{:?}", key))))
+                .collect(),
+            ReflectRef::Value(_) => Err(Error::RuntimeError(format!("{:?} has no fields or elements to iterate", self))),
+        })
+    }
+
+    /// Calls `f` with the value this reference points to, borrowing it for the duration.
+    fn with_reflect<R>(&self, world: &World, f: impl FnOnce(&dyn Reflect) -> Result<R>) -> Result<R> {
+        let _borrow = self.borrow()?;
+
+        f(self.reflect(world)?)
+    }
+
+    /// The variant name and payload of the enum this reference points to, or `None` if it doesn't
+    /// point to one (including when its path doesn't lead anywhere yet).
+    fn enum_variant<'lua>(&self, lua: &'lua Lua) -> Result<Option<(String<'lua>, Value<'lua>)>> {
+        let world = self.world.read()?;
+        let _borrow = self.borrow()?;
+
+        match self.reflect(&world) {
+            Ok(value) => convert::enum_to_lua(lua, &world, value),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Reads `key` of an enum variant: `variant` is the variant's name, anything else a field of
+    /// its payload. Fields read this way are copies; assign the whole enum to change them.
+    fn variant_field<'lua>(&self, variant: String<'lua>, payload: Value<'lua>, key: Value<'lua>) -> Result<Value<'lua>> {
+        if let Value::String(name) = &key {
+            if name.as_bytes() == b"variant" {
+                return Ok(Value::String(variant));
+            }
+        }
+
+        let field = match payload {
+            Value::Table(payload) => payload.get(key.clone())?,
+            _ => Nil,
+        };
+
+        match field {
+            Nil => Err(Error::RuntimeError(format!("Variant {} of {:?} has no field {}", variant.to_str()?, self, key_label(&key)?))),
+            field => Ok(field),
+        }
+    }
+
+    /// Assigns `value` to the part `segment` of the value this reference points to.
+    fn set<'lua>(&self, lua: &'lua Lua, segment: PathSegment, value: Value<'lua>) -> Result<()> {
+        let target = self.child(segment);
+
+        // Assigning one past the end of a list appends to it, as with a table
+        if let Some(PathSegment::Index(index)) = target.path.last() {
+            let world = self.world.read()?;
+
+            if self.len(&world)? == Some(*index) && target.with_reflect(&world, |_| Ok(())).is_err() {
+                drop(world);
+                return self.push(lua, value);
+            }
+        }
+
+        target.assign(lua, value)
+    }
+
+    /// Assigns `value` to the value this reference points to.
+    fn assign<'lua>(&self, lua: &'lua Lua, value: Value<'lua>) -> Result<()> {
+        let world = self.world.read()?;
+
+        // The rvalue is fully built before the lvalue is borrowed mutably, so reading other
+        // references (even into this same component) never overlaps with the write below
+        let rvalue = {
+            let _borrow = self.borrow()?;
+
+            let lvalue = match (self.reflect(&world), self.path.last()) {
+                (Ok(lvalue), _) => lvalue,
+                // Reflection can't add entries to maps of arbitrary types
+                (Err(_), Some(PathSegment::Key(..))) => 
+                    return Err(Error::RuntimeError(format!("Cannot assign to {:?}: new keys can't be added to reflected maps", self))),
+                (Err(err), _) => return Err(err),
+            };
+
+            convert::lua_to_reflect(lua, &world, value, lvalue)?
+        };
+
+        let _borrow = self.borrow_mut()?;
+
+        let comp_ref = 
+            // SAFETY: Every reference scripts take into a component or resource is tracked, and the
+            // exclusive borrow held until the end of this function rules out any other one into this
+            // one. The read lock on the world rules out structural changes such as despawning.
+            unsafe { self.reflect_base_unchecked_mut(&world) }?;
+
+        let lvalue = 
+            path::walk_mut(comp_ref, &self.path)
+            .ok_or_else(|| self.invalid_path())?;
+
+        if let ReflectRef::Value(_) = lvalue.reflect_ref() {
+            // `lua_to_reflect` produced exactly the lvalue's type
+            lvalue.set(rvalue)
+            .map_err(|rvalue| Error::RuntimeError(format!("Failed to assign rvalue {} to lvalue {}", rvalue.type_name(), lvalue.type_name())))?;
+        } else {
+            // `lua_to_reflect` produced a dynamic value shaped like the lvalue, which is applied field by field.
+            // Reflection panics if a part doesn't fit, which `lua_to_reflect` can't rule out for the
+            // elements of empty lists and maps, so that is checked first
+            if !path::fits(lvalue, &*rvalue) {
+                return Err(Error::RuntimeError(format!("Cannot assign rvalue {} to {:?}: its elements are not of the lvalue's types", rvalue.type_name(), self)));
+            }
+
+            lvalue.apply(&*rvalue);
+        }
+
+        Ok(())
+    }
+
+    /// The Rust method `name` registered for the type of the value this reference points to.
+    fn method(&self, name: &str) -> Result<Option<LuaMethod>> {
+        let world = self.world.read()?;
+
+        let methods = match world.get_resource::<LuaMethodRegistry>() {
+            Some(methods) => methods,
+            None => return Ok(None),
+        };
+
+        match self.with_reflect(&world, |value| Ok(methods.get(value, name))) {
+            Ok(method) => Ok(method),
+            // Paths that don't lead anywhere have no methods, and fail as fields instead
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Calls `method` on the value this reference points to, which stays borrowed mutably until
+    /// it returns.
+    fn call<'lua>(&self, lua: &'lua Lua, method: &LuaMethod, args: MultiValue<'lua>) -> Result<MultiValue<'lua>> {
+        // References to numbers, vectors... are passed as their value, read before the write below
+        let args = convert::operands(lua, args);
+
+        let world = self.world.read()?;
+        let _borrow = self.borrow_mut()?;
+
+        // SAFETY: See `assign`
+        let value = unsafe { self.reflect_base_unchecked_mut(&world) }?;
+        let value = path::walk_mut(value, &self.path).ok_or_else(|| self.invalid_path())?;
+
+        method(lua, value, args)
+    }
+
+    /// Converts `value` into a new element for the list this reference points to.
+    ///
+    /// Lists can only grow by elements of their exact type, which in bevy 0.6 only values
+    /// reflected as a whole (numbers, strings, math types...) can be converted into.
+    fn list_element<'lua>(&self, lua: &'lua Lua, world: &World, list: &dyn List, value: Value<'lua>) -> Result<Box<dyn Reflect>> {
+        let element = convert::lua_to_element(lua, world, value, list)?;
+
+        match path::fits_list(list, &*element) {
+            true => Ok(element),
+            false =>
+                Err(Error::RuntimeError(format!("Cannot add a {} to {:?}: only elements reflected as values of the list's type can be added", element.type_name(), self))),
+        }
+    }
+
+    /// Appends `value` to the list this reference points to.
+    fn push<'lua>(&self, lua: &'lua Lua, value: Value<'lua>) -> Result<()> {
+        let world = self.world.read()?;
+
+        let element = self.with_reflect(&world, |list| match list.reflect_ref() {
+            ReflectRef::List(list) => self.list_element(lua, &world, list, value),
+            _ => Err(Error::RuntimeError(format!("{:?} is not a list", self))),
+        })?;
+
+        let _borrow = self.borrow_mut()?;
+
+        // SAFETY: See `assign`
+        let list = unsafe { self.list_unchecked_mut(&world) }?;
+
+        // `list_element` checked the element is of the list's type, which pushing panics on otherwise
+        list.push(element);
+
+        Ok(())
+    }
+
+    /// Inserts `value` into the list this reference points to at the 0-based `index`, shifting
+    /// the following elements along.
+    fn insert<'lua>(&self, lua: &'lua Lua, index: usize, value: Value<'lua>) -> Result<()> {
+        let world = self.world.read()?;
+
+        let len = self.len(&world)?.unwrap_or(0);
+        if index == len {
+            drop(world);
+            return self.push(lua, value);
+        }
+        if index > len {
+            return Err(Error::RuntimeError(format!("Cannot insert at index {} of {:?}, which has {} elements", index + 1, self, len)));
+        }
+
+        // The list grows by a copy of its last element, which the shift then overwrites. Only copies
+        // of values are of the element type rather than dynamic, and so can be pushed.
+        let (element, last) = self.with_reflect(&world, |list| match list.reflect_ref() {
+            ReflectRef::List(list) => {
+                let element = self.list_element(lua, &world, list, value)?;
+                let last = list.get(len - 1).ok_or_else(|| self.invalid_path())?.clone_value();
+
+                match path::fits_list(list, &*last) {
+                    true => Ok((element, last)),
+                    false => Err(Error::RuntimeError(format!("Cannot insert into {:?}: only lists of values can grow", self))),
+                }
+            },
+            _ => Err(Error::RuntimeError(format!("{:?} is not a list", self))),
+        })?;
+
+        let _borrow = self.borrow_mut()?;
+
+        // SAFETY: See `assign`
+        let list = unsafe { self.list_unchecked_mut(&world) }?;
+        list.push(last);
+
+        for to in (index + 1..=len).rev() {
+            let shifted = list.get(to - 1).ok_or_else(|| self.invalid_path())?.clone_value();
+            list.get_mut(to).ok_or_else(|| self.invalid_path())?.apply(&*shifted);
+        }
+
+        list.get_mut(index).ok_or_else(|| self.invalid_path())?.apply(&*element);
+
+        Ok(())
+    }
+}
+
+/// A Lua function calling `method` on the reference it is called on, as in `comp:method(...)`.
+fn bind<'lua>(lua: &'lua Lua, method: LuaMethod) -> Result<Function<'lua>> {
+    lua.create_function(move |lua, (this, args): (AnyUserData, MultiValue)| {
+        let this = this.borrow::<LuaCompRef>()?.clone();
+
+        this.call(lua, &method, args)
+    })
+}
+
+/// Wraps a method of a value read through a reference, such as `tf.translation:length()`, so
+/// it is called with that value as `self` and references among its arguments read as well.
+fn by_value<'lua>(lua: &'lua Lua, method: Function<'lua>) -> Result<Function<'lua>> {
+    let method = lua.create_registry_value(method)?;
+
+    lua.create_function(move |lua, args: MultiValue| {
+        lua.registry_value::<Function>(&method)?.call::<_, MultiValue>(convert::operands(lua, args))
+    })
+}
+
+/// Converts a 1-based Lua index into a 0-based one.
+fn lua_index(key: &Value) -> Result<usize> {
+    let index = match key {
+        Value::Integer(int) => *int,
+        Value::Number(float) if float.fract() == 0.0 => *float as i64,
+        key => return Err(Error::RuntimeError(format!("Expected an index, got a {}", key.type_name()))),
+    };
+
+    if index < 1 {
+        return Err(Error::RuntimeError(format!("Index {} is out of range; indices start at 1", index)));
+    }
+
+    Ok((index - 1) as usize)
+}
+
+/// How a key is shown in paths and errors.
+fn key_label(key: &Value) -> Result<std::string::String> {
+    Ok(match key {
+        Value::String(key) => format!("{:?}", key.to_str()?),
+        Value::Integer(int) => int.to_string(),
+        Value::Number(float) => float.to_string(),
+        Value::Boolean(bool) => bool.to_string(),
+        key => key.type_name().to_string(),
+    })
+}
+
+/// Iterates over `segments` of `reference`, yielding each key and a reference to what it selects.
+fn iterate<'lua>(lua: &'lua Lua, reference: &LuaCompRef, segments: Vec<PathSegment>) -> Result<Function<'lua>> {
+    let reference = reference.clone();
+    let mut segments = segments.into_iter();
+
+    lua.create_function_mut(move |lua, ()| {
+        let segment = match segments.next() {
+            Some(segment) => segment,
+            None => return Ok((Nil, Nil)),
+        };
+
+        let world = reference.world.read()?;
+
+        let key = match &segment {
+            PathSegment::Field(name) => name.as_str().to_lua(lua)?,
+            PathSegment::Index(index) => (index + 1).to_lua(lua)?,
+            PathSegment::Key(key, _) => convert::reflect_to_lua(lua, &world, &reference, &**key)?,
+        };
+
+        Ok((key, reference.child(segment).into_field(lua, &world)?))
+    })
+}
+
+impl std::fmt::Debug for LuaCompRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path: std::string::String = self.path.iter().map(|segment| format!("{:?}", segment)).collect();
+
+        match &self.base {
+            ReferenceBase::Component(entity, _) => f.write_fmt(format_args!("&{:?}.{}{}", entity, self.comp_name, path)),
+            ReferenceBase::Resource { .. } => f.write_fmt(format_args!("&{}{}", self.comp_name, path)),
+            ReferenceBase::Owned(value) => f.write_fmt(format_args!("{}{}", value.type_name(), path)),
+        }
+    }
+}
+
+fn userdata<T>(value: Value) -> Result<AnyUserData> {
+    match value {
+        Value::UserData(userdata) => Ok(userdata),
+        _ => Err(Error::FromLuaConversionError { from: value.type_name(), to: std::any::type_name::<T>(), message: None })
+    }
+}
+
+impl UserData for LuaCompRef {
+    fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field_with(MetaMethod::Index, |lua| {
+            lua.create_function(|lua, (base, key): (Value, Value)| {
+                let any = userdata::<LuaCompRef>(base)?;
+                let base = any.borrow::<LuaCompRef>()?;
+
+                if let Value::String(name) = &key {
+                    if let Some(method) = base.method(name.to_str()?)? {
+                        return Ok(Value::Function(bind(lua, method)?));
+                    }
+                }
+
+                if let Some((variant, payload)) = base.enum_variant(lua)? {
+                    return base.variant_field(variant, payload, key);
+                }
+
+                let world = base.world.read()?;
+
+                // Values with a Lua equivalent have no reflected fields, and are indexed through it: `tf.translation.x`
+                if let Some(leaf) = base.leaf_value(lua, &world)? {
+                    return match operator(lua, "index", leaf, key)? {
+                        Value::Function(method) => Ok(Value::Function(by_value(lua, method)?)),
+                        value => Ok(value),
+                    };
+                }
+
+                let child = base.child(base.segment(lua, &world, key)?);
+
+                // Missing list elements and map entries read as nil, as they would in a table
+                if let Some(PathSegment::Index(_) | PathSegment::Key(..)) = child.path.last() {
+                    if child.with_reflect(&world, |_| Ok(())).is_err() {
+                        return Ok(Nil);
+                    }
+                }
+
+                child.into_field(lua, &world)
+            })
+        });
+
+        fields.add_meta_field_with(MetaMethod::NewIndex, |lua| {
+            lua.create_function(|lua, (base, key, value): (Value, Value, Value)| {
+                let any = userdata::<LuaCompRef>(base)?;
+                let base = any.borrow::<LuaCompRef>()?;
+
+                let leaf = base.leaf_value(lua, &*base.world.read()?)?;
+
+                // Values with a Lua equivalent are copied out, changed and written back whole
+                if let Some(leaf) = leaf {
+                    let operators: Table = lua.named_registry_value(OPERATORS)?;
+                    let leaf: Value = operators.get::<_, Function>("newindex")?.call((leaf, key, value))?;
+
+                    base.assign(lua, leaf)?;
+                    return Ok(Nil);
+                }
+
+                let segment = {
+                    let world = base.world.read()?;
+                    base.segment(lua, &world, key)?
+                };
+
+                base.set(lua, segment, value)?;
+
+                Ok(Nil)
+            })
+        });
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        // References to values with a Lua equivalent print as that value, anything else as its path
+        methods.add_meta_method(MetaMethod::ToString, |lua, this, ()| {
+            let world = this.world.read()?;
+
+            match this.with_reflect(&world, |value| convert::value_to_lua(lua, &world, value).transpose())? {
+                Some(value) => lua.globals().get::<_, Function>("tostring")?.call(value),
+                None => format!("{:?}", this).to_lua(lua),
+            }
+        });
+
+        // References to values with a Lua equivalent act like that value in arithmetic. LuaJIT only
+        // calls the comparison metamethods with references on both sides, never with a plain value.
+        for (meta, name) in [
+            (MetaMethod::Add, "add"),
+            (MetaMethod::Sub, "sub"),
+            (MetaMethod::Mul, "mul"),
+            (MetaMethod::Div, "div"),
+            (MetaMethod::Mod, "mod"),
+            (MetaMethod::Pow, "pow"),
+            (MetaMethod::Unm, "unm"),
+            (MetaMethod::Concat, "concat"),
+            (MetaMethod::Lt, "lt"),
+            (MetaMethod::Le, "le"),
+        ] {
+            methods.add_meta_function(meta, move |lua, (lhs, rhs): (Value, Value)| operator(lua, name, lhs, rhs));
+        }
+
+        // Only values can be compared; references to anything else are never equal
+        methods.add_meta_function(MetaMethod::Eq, |lua, (lhs, rhs): (Value, Value)| {
+            match operator(lua, "eq", lhs, rhs) {
+                Ok(equal) => Ok(equal),
+                Err(_) => Ok(Value::Boolean(false)),
+            }
+        });
+
+        // Lists, maps and tuples have a length like tables, anything else is treated as a value
+        methods.add_meta_method(MetaMethod::Len, |lua, this, _: Value| {
+            let len = this.len(&*this.world.read()?)?;
+
+            match len {
+                Some(len) => len.to_lua(lua),
+                None => operator(lua, "len", this.clone().to_lua(lua)?, Nil),
+            }
+        });
+
+        // for name, field in comp:pairs() do
+        methods.add_method("pairs", |lua, this, ()| {
+            let entries = this.entries(&*this.world.read()?)?;
+
+            iterate(lua, this, entries)
+        });
+
+        // for i, element in list:ipairs() do
+        methods.add_method("ipairs", |lua, this, ()| {
+            let entries = this.entries(&*this.world.read()?)?;
+
+            if let Some(PathSegment::Field(_) | PathSegment::Key(..)) = entries.first() {
+                return Err(Error::RuntimeError(format!("{:?} is not a list or tuple", this)));
+            }
+
+            iterate(lua, this, entries)
+        });
+
+        methods.add_method("push", |lua, this, value: Value| {
+            this.push(lua, value)
+        });
+
+        methods.add_method("insert", |lua, this, (index, value): (Value, Value)| {
+            this.insert(lua, lua_index(&index)?, value)
+        });
+
+        // Reflected lists can only grow in bevy 0.6, so this only explains why removing fails
+        methods.add_method("remove", |_, this, _: MultiValue| -> Result<()> {
+            Err(Error::RuntimeError(format!("Cannot remove elements from {:?}: reflected lists can't shrink", this)))
+        });
+
+        // Whether an enum is currently the variant `name`
+        methods.add_method("is", |lua, this, name: String| {
+            match this.enum_variant(lua)? {
+                Some((variant, _)) => Ok(variant.as_bytes() == name.as_bytes()),
+                None => Err(Error::RuntimeError(format!("{:?} is not an enum", this))),
+            }
+        });
+
+        // Snapshots the value into plain Lua values, so it stays the same while the component changes
+        methods.add_method("clone", |lua, this, ()| {
+            let world = this.world.read()?;
+
+            this.with_reflect(&world, |value| convert::reflect_to_lua(lua, &world, this, value))
+        })
+    }
+}
+
+/// The named registry value holding [`OPERATOR_SOURCE`].
+const OPERATORS: &str = "bevy_mod_lua.operators";
+
+/// Applies each operator to plain values, once references have been replaced by what they point to.
+const OPERATOR_SOURCE: &str = r#"
+    return {
+        add = function(a, b) return a + b end,
+        sub = function(a, b) return a - b end,
+        mul = function(a, b) return a * b end,
+        div = function(a, b) return a / b end,
+        mod = function(a, b) return a % b end,
+        pow = function(a, b) return a ^ b end,
+        unm = function(a) return -a end,
+        len = function(a) return #a end,
+        concat = function(a, b) return a .. b end,
+        eq = function(a, b) return a == b end,
+        lt = function(a, b) return a < b end,
+        le = function(a, b) return a <= b end,
+        index = function(a, k) return a[k] end,
+        newindex = function(a, k, v) a[k] = v return a end,
+    }
+"#;
+
+fn register_operators(lua: &Lua) -> Result<()> {
+    let operators: Table = lua.load(OPERATOR_SOURCE).set_name("=operators")?.eval()?;
+
+    lua.set_named_registry_value(OPERATORS, operators)
+}
+
+fn operator<'lua>(lua: &'lua Lua, name: &str, lhs: Value<'lua>, rhs: Value<'lua>) -> Result<Value<'lua>> {
+    let (lhs, rhs) = (convert::operand(lua, lhs)?, convert::operand(lua, rhs)?);
+
+    let operators: Table = lua.named_registry_value(OPERATORS)?;
+
+    operators.get::<_, Function>(name)?.call((lhs, rhs))
+}
+
+/// Runs one lifecycle callback of the script `module` for `entity`, if the script defines it.
+fn run_callback<'lua, A: ToLuaMulti<'lua>>(
+    lua: &'lua Lua,
+    module: &Table<'lua>,
+    name: &str,
+    entity: LuaEntity,
+    args: A,
+) -> Result<()> {
+    if let Some(callback) = module.get::<_, Option<Function>>(name)? {
+        lua.globals().set("entity", entity)?;
+        let result = callback.call::<_, ()>(args);
+        lua.globals().set("entity", Nil)?;
+        result?;
+    }
+
+    Ok(())
+}
+
+fn lua_host(world: &mut World) {
+    // Everything is read up front so the resources stay in the world for host functions to use
+    let lua = world.get_resource::<BevyLua>().unwrap().clone();
+    let world_ref = world.get_resource::<LuaWorldRef>().unwrap().clone();
+    let delta_seconds = world.get_resource::<Time>().unwrap().delta_seconds();
+
+    let scripted: Vec<(Entity, Vec<HandleId>)> = 
+        world.query::<(Entity, &LuaScripts)>()
+        .iter(world)
+        .map(|(entity, scripts)| (entity, scripts.0.iter().map(|script| script.id).collect()))
+        .collect();
+
+    // Entities that were despawned or had a script removed since last frame
+    let despawned = world.get_resource_mut::<LuaScriptInstances>().unwrap().retain(&scripted);
+
+    let lua = lua.lock().expect("Failed to lock Lua mutex");
+    let mut errors = Vec::new();
+
+    // Nothing runs until the script has finished loading and compiling
+    let mut modules = HashMap::default();
+    {
+        let cache = world.get_resource::<LuaScriptCache>().unwrap();
+        let used = scripted.iter().flat_map(|(_, scripts)| scripts.iter()).chain(despawned.iter().map(|(_, script)| script));
+
+        for script in used {
+            match cache.get(&lua, *script) {
+                Ok(Some(module)) => {
+                    modules.insert(*script, module);
+                },
+                Ok(None) => {},
+                Err(err) => {
+                    error!("Failed to read Lua script module: {}", err);
+                    errors.push(LuaScriptError::new(Handle::weak(*script), None, &err));
+                },
+            }
+        }
+    }
+
+    // Instances that are new and need `on_spawn`
+    let mut spawned = HashSet::default();
+    {
+        let mut instances = world.get_resource_mut::<LuaScriptInstances>().unwrap();
+
+        for (entity, scripts) in &scripted {
+            for script in scripts {
+                if modules.contains_key(script) && instances.spawn(*entity, *script) {
+                    spawned.insert((*entity, *script));
+                }
+            }
+        }
+    }
+
+    // Scripts borrow the world through a reference that is revoked when the scope ends, instead of
+    // taking the world out of the app
+    world_ref.scope(world, || {
+        // A failing script is reported and skipped, the rest keep running
+        let mut report = |entity: Entity, script: HandleId, result: Result<()>| {
+            if let Err(err) = result {
+                error!("Lua script failed on entity {:?}: {}", entity, err);
+                errors.push(LuaScriptError::new(Handle::weak(script), Some(entity), &err));
+            }
+        };
+
+        if let Err(err) = lua.globals().set("world", world_ref.clone()) {
+            error!("Failed to set Lua global `world`: {}", err);
+        }
+        if let Err(err) = lua.globals().set("deltaTime", delta_seconds) {
+            error!("Failed to set Lua global `deltaTime`: {}", err);
+        }
+
+        for (entity, script) in despawned {
+            if let Some(module) = modules.get(&script) {
+                let lua_entt = LuaEntity { entity, world: world_ref.clone() };
+                report(entity, script, run_callback(&lua, module, "on_despawn", lua_entt, ()));
+            }
+        }
+
+        for (entity, scripts) in &scripted {
+            for script in scripts {
+                let module = match modules.get(script) {
+                    Some(module) => module,
+                    None => continue,
+                };
+
+                if spawned.contains(&(*entity, *script)) {
+                    let lua_entt = LuaEntity { entity: *entity, world: world_ref.clone() };
+                    report(*entity, *script, run_callback(&lua, module, "on_spawn", lua_entt, ()));
+                }
+
+                let lua_entt = LuaEntity { entity: *entity, world: world_ref.clone() };
+                report(*entity, *script, run_callback(&lua, module, "on_update", lua_entt, delta_seconds));
+            }
+        }
+
+        if let Err(err) = lua.globals().set("world", Nil) {
+            error!("Failed to clear Lua global `world`: {}", err);
+        }
+    });
+
+    if let Some(mut events) = world.get_resource_mut::<Events<LuaScriptError>>() {
+        for error in errors {
+            events.send(error);
+        }
+    }
+}
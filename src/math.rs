@@ -0,0 +1,777 @@
+use bevy::prelude::*;
+use mlua::*;
+
+use std::convert::TryFrom;
+use std::ops::Neg;
+
+use crate::convert;
+use crate::converter::proxy_from_lua;
+use crate::converter::proxy_to_lua;
+use crate::converter::LuaConverterRegistry;
+
+/// Wraps a math type in a userdata named `Lua<Type>`.
+///
+/// `fields` become Lua fields, read and written either directly or through a getter and setter
+/// (`r: f32 = r / set_r`). `consts` and `constructors` are exposed through a global table named
+/// after the type, so scripts can write `Vec3.ZERO` or `Quat.from_rotation_y(angle)`.
+///
+/// `methods` are called with `:`, `meta` maps unary metamethods to methods (`Unm: neg`) and `ops`
+/// lists the operand types each arithmetic metamethod accepts, either way around. `checked_methods`,
+/// `checked_meta` and `checked_ops` are the same for integer types, raising an error on overflow
+/// instead. `__eq` compares two values of the same type.
+macro_rules! impl_lua_newtype {
+    (
+        $ty:ident
+        $(fields {
+            $($field:ident: $field_ty:ty $(= $get:ident / $set:ident)?),* $(,)?
+        })?
+        $(consts {
+            $($constant:ident),* $(,)?
+        })?
+        $(constructors {
+            $($ctor:ident($($arg:ident: $arg_ty:ty),* $(,)?)),* $(,)?
+        })?
+        $(methods {
+            $($method:ident($($method_arg:ident: $method_arg_ty:ty),* $(,)?)),* $(,)?
+        })?
+        $(checked_methods {
+            $($checked_method:ident($($checked_method_arg:ident: $checked_method_arg_ty:ty),* $(,)?)),* $(,)?
+        })?
+        $(meta {
+            $($meta:ident: $meta_fn:ident),* $(,)?
+        })?
+        $(checked_meta {
+            $($checked_meta:ident: $checked_meta_fn:ident),* $(,)?
+        })?
+        $(ops {
+            $($op:ident { $(($lhs:ty, $rhs:ty)),* $(,)? }),* $(,)?
+        })?
+        $(checked_ops {
+            $($checked_op:ident { $(($checked_lhs:ty, $checked_rhs:ty)),* $(,)? }),* $(,)?
+        })?
+        $(Debug($target:ident))?
+    ) => {
+        paste::paste! {
+            #[derive(Clone, Copy)]
+            pub struct [<Lua $ty>](pub $ty);
+
+            impl UserData for [<Lua $ty>] {
+                $(fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+                    $(
+                        impl_lua_newtype!(@field fields $field $field_ty $(, $get, $set)?);
+                    )*
+                })?
+
+                fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+                    $($(
+                        methods.add_method(stringify!($method), |lua, this, args: MultiValue| {
+                            let ($($method_arg,)*): ($(<$method_arg_ty as LuaNewtype>::Newtype,)*) = FromLuaMulti::from_lua_multi(convert::operands(lua, args), lua)?;
+
+                            Ok(LuaNewtype::wrap(this.0.$method($(<$method_arg_ty as LuaNewtype>::unwrap($method_arg)),*)))
+                        });
+                    )*)?
+
+                    $($(
+                        methods.add_method(stringify!($checked_method), |lua, this, args: MultiValue| {
+                            let ($($checked_method_arg,)*): ($(<$checked_method_arg_ty as LuaNewtype>::Newtype,)*) = FromLuaMulti::from_lua_multi(convert::operands(lua, args), lua)?;
+
+                            let result =
+                                this.0.[<checked_ $checked_method>]($(<$checked_method_arg_ty as LuaNewtype>::unwrap($checked_method_arg)),*)
+                                .ok_or_else(|| Error::RuntimeError(format!(
+                                    "Cannot {} {:?}: the result overflows", stringify!($checked_method), this.0
+                                )))?;
+
+                            Ok(LuaNewtype::wrap(result))
+                        });
+                    )*)?
+
+                    $($(
+                        methods.add_meta_method(MetaMethod::$meta, |_, this, _: Value| {
+                            Ok(LuaNewtype::wrap(this.0.$meta_fn()))
+                        });
+                    )*)?
+
+                    $($(
+                        methods.add_meta_method(MetaMethod::$checked_meta, |_, this, _: Value| {
+                            let result =
+                                this.0.[<checked_ $checked_meta_fn>]()
+                                .ok_or_else(|| Error::RuntimeError(format!(
+                                    "Cannot {} {:?}: the result overflows", stringify!($checked_meta_fn), this.0
+                                )))?;
+
+                            Ok(LuaNewtype::wrap(result))
+                        });
+                    )*)?
+
+                    $($(
+                        methods.add_meta_function(MetaMethod::$op, |lua, (lhs, rhs): (Value, Value)| {
+                            let (lhs, rhs) = (convert::operand(lua, lhs)?, convert::operand(lua, rhs)?);
+
+                            $(
+                                if let (Some(lhs), Some(rhs)) = (<$lhs as LuaNewtype>::operand(&lhs), <$rhs as LuaNewtype>::operand(&rhs)) {
+                                    return LuaNewtype::wrap(std::ops::$op::[<$op:lower>](lhs, rhs)).to_lua(lua);
+                                }
+                            )*
+
+                            Err(Error::RuntimeError(format!(
+                                "Cannot {} a {} and a {}", stringify!([<$op:lower>]), lhs.type_name(), rhs.type_name()
+                            )))
+                        });
+                    )*)?
+
+                    $($(
+                        methods.add_meta_function(MetaMethod::$checked_op, |lua, (lhs, rhs): (Value, Value)| {
+                            let (lhs, rhs) = (convert::operand(lua, lhs)?, convert::operand(lua, rhs)?);
+
+                            $(
+                                if let (Some(lhs), Some(rhs)) = (<$checked_lhs as LuaNewtype>::operand(&lhs), <$checked_rhs as LuaNewtype>::operand(&rhs)) {
+                                    let result =
+                                        CheckedArith::checked_op(lhs, rhs, |lhs, rhs| lhs.[<checked_ $checked_op:lower>](rhs))
+                                        .ok_or_else(|| Error::RuntimeError(format!(
+                                            "Cannot {} {:?} and {:?}: {}", stringify!([<$checked_op:lower>]), lhs, rhs,
+                                            match stringify!($checked_op) {
+                                                "Div" => "it divides by zero or overflows",
+                                                _ => "the result overflows",
+                                            }
+                                        )))?;
+
+                                    return LuaNewtype::wrap(result).to_lua(lua);
+                                }
+                            )*
+
+                            Err(Error::RuntimeError(format!(
+                                "Cannot {} a {} and a {}", stringify!([<$checked_op:lower>]), lhs.type_name(), rhs.type_name()
+                            )))
+                        });
+                    )*)?
+
+                    methods.add_meta_function(MetaMethod::Eq, |lua, (lhs, rhs): (Value, Value)| {
+                        let (lhs, rhs) = (convert::operand(lua, lhs)?, convert::operand(lua, rhs)?);
+
+                        Ok(match (<$ty as LuaNewtype>::operand(&lhs), <$ty as LuaNewtype>::operand(&rhs)) {
+                            (Some(lhs), Some(rhs)) => lhs == rhs,
+                            _ => false,
+                        })
+                    });
+
+                    $(impl_lua_newtype!(@Debug $target methods);)?
+                }
+            }
+
+            impl [<Lua $ty>] {
+                fn register(lua: &Lua) -> Result<()> {
+                    let table = lua.create_table()?;
+
+                    $($(
+                        table.set(stringify!($constant), [<Lua $ty>]($ty::$constant))?;
+                    )*)?
+
+                    $($(
+                        table.set(stringify!($ctor), lua.create_function(|lua, args: MultiValue| {
+                            let ($($arg,)*): ($(<$arg_ty as LuaNewtype>::Newtype,)*) = FromLuaMulti::from_lua_multi(convert::operands(lua, args), lua)?;
+
+                            Ok(LuaNewtype::wrap($ty::$ctor($(<$arg_ty as LuaNewtype>::unwrap($arg)),*)))
+                        })?)?;
+                    )*)?
+
+                    lua.globals().set(stringify!($ty), table)
+                }
+            }
+
+            impl LuaNewtype for $ty {
+                type Newtype = [<Lua $ty>];
+
+                fn wrap(self) -> Self::Newtype {
+                    [<Lua $ty>](self)
+                }
+
+                fn unwrap(newtype: Self::Newtype) -> Self {
+                    newtype.0
+                }
+
+                fn operand(value: &Value) -> Option<Self> {
+                    match value {
+                        Value::UserData(userdata) => userdata.borrow::<[<Lua $ty>]>().ok().map(|newtype| newtype.0),
+                        _ => None,
+                    }
+                }
+            }
+        }
+    };
+
+    (@field $fields:ident $field:ident $field_ty:ty, $get:ident, $set:ident) => {
+        $fields.add_field_method_get(stringify!($field), |_, this| Ok(<$field_ty as LuaNewtype>::wrap(this.0.$get())));
+        $fields.add_field_method_set(stringify!($field), |_, this, value: <$field_ty as LuaNewtype>::Newtype| {
+            this.0.$set(<$field_ty as LuaNewtype>::unwrap(value));
+            Ok(())
+        });
+    };
+
+    (@field $fields:ident $field:ident $field_ty:ty) => {
+        $fields.add_field_method_get(stringify!($field), |_, this| Ok(<$field_ty as LuaNewtype>::wrap(this.0.$field)));
+        $fields.add_field_method_set(stringify!($field), |_, this, value: <$field_ty as LuaNewtype>::Newtype| {
+            this.0.$field = <$field_ty as LuaNewtype>::unwrap(value);
+            Ok(())
+        });
+    };
+
+    (@Debug newtype $methods:ident) => {
+        $methods.add_meta_method(MetaMethod::ToString, |lua, this, ()| {
+            format!("{:?}", this).to_lua(lua)
+        })
+    };
+
+    (@Debug original $methods:ident) => {
+        $methods.add_meta_method(MetaMethod::ToString, |lua, this, ()| {
+            format!("{:?}", this.0).to_lua(lua)
+        })
+    }
+}
+
+/// A type as it is passed to and from Lua: math types and types deriving `LuaProxy` as their
+/// `Lua<Type>` userdata, and primitives as themselves.
+pub trait LuaNewtype: Sized {
+    type Newtype;
+
+    fn wrap(self) -> Self::Newtype;
+
+    fn unwrap(newtype: Self::Newtype) -> Self;
+
+    /// Reads an operand of an arithmetic metamethod, which may be of any type.
+    fn operand(value: &Value) -> Option<Self>;
+}
+
+macro_rules! impl_lua_newtype_self {
+    ($($ty:ty => |$value:ident| $operand:expr),* $(,)?) => {
+        $(
+            impl LuaNewtype for $ty {
+                type Newtype = $ty;
+
+                fn wrap(self) -> Self::Newtype {
+                    self
+                }
+
+                fn unwrap(newtype: Self::Newtype) -> Self {
+                    newtype
+                }
+
+                fn operand($value: &Value) -> Option<Self> {
+                    $operand
+                }
+            }
+        )*
+    };
+}
+
+/// A Lua number as an integer of type `I`, if it is whole and fits.
+fn integer_operand<I: TryFrom<i64>>(value: &Value) -> Option<I> {
+    // Casting to `i64` saturates outside [-2^63, 2^63) instead of failing
+    let i64_range = i64::MIN as f64..-(i64::MIN as f64);
+
+    match value {
+        Value::Integer(int) => I::try_from(*int).ok(),
+        Value::Number(float) if float.fract() == 0.0 && i64_range.contains(float) => I::try_from(*float as i64).ok(),
+        _ => None,
+    }
+}
+
+impl_lua_newtype_self! {
+    f32 => |value| match value {
+        Value::Integer(int) => Some(*int as f32),
+        Value::Number(float) => Some(*float as f32),
+        _ => None,
+    },
+    i8 => |value| integer_operand(value),
+    i16 => |value| integer_operand(value),
+    i32 => |value| integer_operand(value),
+    i64 => |value| integer_operand(value),
+    isize => |value| integer_operand(value),
+    u8 => |value| integer_operand(value),
+    u16 => |value| integer_operand(value),
+    u32 => |value| integer_operand(value),
+    u64 => |value| integer_operand(value),
+    usize => |value| integer_operand(value),
+    bool => |value| match value {
+        Value::Boolean(bool) => Some(*bool),
+        _ => None,
+    },
+    f64 => |value| match value {
+        Value::Integer(int) => Some(*int as f64),
+        Value::Number(float) => Some(*float),
+        _ => None,
+    },
+    std::string::String => |value| match value {
+        Value::String(string) => string.to_str().ok().map(str::to_string),
+        _ => None,
+    },
+}
+
+impl_lua_newtype! {
+    Vec2
+    fields { x: f32, y: f32 }
+    consts { ZERO, ONE, X, Y }
+    constructors { new(x: f32, y: f32), splat(v: f32) }
+    methods {
+        dot(rhs: Vec2),
+        length(),
+        length_squared(),
+        normalize(),
+        normalize_or_zero(),
+        distance(rhs: Vec2),
+        lerp(rhs: Vec2, s: f32),
+        abs(),
+        min(rhs: Vec2),
+        max(rhs: Vec2),
+        clamp(min: Vec2, max: Vec2),
+        angle_between(rhs: Vec2),
+        perp(),
+        extend(z: f32),
+    }
+    meta { Unm: neg, Len: length }
+    ops {
+        Add { (Vec2, Vec2), (Vec2, f32), (f32, Vec2) },
+        Sub { (Vec2, Vec2), (Vec2, f32), (f32, Vec2) },
+        Mul { (Vec2, Vec2), (Vec2, f32), (f32, Vec2) },
+        Div { (Vec2, Vec2), (Vec2, f32), (f32, Vec2) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    Vec3
+    fields { x: f32, y: f32, z: f32 }
+    consts { ZERO, ONE, X, Y, Z }
+    constructors { new(x: f32, y: f32, z: f32), splat(v: f32) }
+    methods {
+        dot(rhs: Vec3),
+        length(),
+        length_squared(),
+        normalize(),
+        normalize_or_zero(),
+        distance(rhs: Vec3),
+        lerp(rhs: Vec3, s: f32),
+        abs(),
+        min(rhs: Vec3),
+        max(rhs: Vec3),
+        clamp(min: Vec3, max: Vec3),
+        cross(rhs: Vec3),
+        angle_between(rhs: Vec3),
+        extend(w: f32),
+        truncate(),
+    }
+    meta { Unm: neg, Len: length }
+    ops {
+        Add { (Vec3, Vec3), (Vec3, f32), (f32, Vec3) },
+        Sub { (Vec3, Vec3), (Vec3, f32), (f32, Vec3) },
+        Mul { (Vec3, Vec3), (Vec3, f32), (f32, Vec3) },
+        Div { (Vec3, Vec3), (Vec3, f32), (f32, Vec3) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    Vec4
+    fields { x: f32, y: f32, z: f32, w: f32 }
+    consts { ZERO, ONE, X, Y, Z, W }
+    constructors { new(x: f32, y: f32, z: f32, w: f32), splat(v: f32) }
+    methods {
+        dot(rhs: Vec4),
+        length(),
+        length_squared(),
+        normalize(),
+        normalize_or_zero(),
+        distance(rhs: Vec4),
+        lerp(rhs: Vec4, s: f32),
+        abs(),
+        min(rhs: Vec4),
+        max(rhs: Vec4),
+        clamp(min: Vec4, max: Vec4),
+        truncate(),
+    }
+    meta { Unm: neg, Len: length }
+    ops {
+        Add { (Vec4, Vec4), (Vec4, f32), (f32, Vec4) },
+        Sub { (Vec4, Vec4), (Vec4, f32), (f32, Vec4) },
+        Mul { (Vec4, Vec4), (Vec4, f32), (f32, Vec4) },
+        Div { (Vec4, Vec4), (Vec4, f32), (f32, Vec4) },
+    }
+    Debug(original)
+}
+
+/// Component-wise arithmetic on integer vectors and their scalars that fails instead of
+/// overflowing.
+trait CheckedArith<Rhs> {
+    type Scalar;
+    type Output;
+
+    fn checked_op(self, rhs: Rhs, op: impl Fn(Self::Scalar, Self::Scalar) -> Option<Self::Scalar>) -> Option<Self::Output>;
+}
+
+/// The dot product of integer vectors, failing instead of overflowing.
+trait CheckedDot {
+    type Scalar;
+
+    fn checked_dot(self, rhs: Self) -> Option<Self::Scalar>;
+}
+
+/// Negation and absolute values of signed integer vectors, failing on `MIN` components.
+trait CheckedSigned: Sized {
+    fn checked_neg(self) -> Option<Self>;
+    fn checked_abs(self) -> Option<Self>;
+}
+
+macro_rules! impl_checked_arith {
+    ($($ty:ident($scalar:ty) { $($field:ident),* }),* $(,)?) => {
+        $(
+            impl CheckedArith<$ty> for $ty {
+                type Scalar = $scalar;
+                type Output = $ty;
+
+                fn checked_op(self, rhs: $ty, op: impl Fn($scalar, $scalar) -> Option<$scalar>) -> Option<$ty> {
+                    Some($ty::new($(op(self.$field, rhs.$field)?),*))
+                }
+            }
+
+            impl CheckedArith<$scalar> for $ty {
+                type Scalar = $scalar;
+                type Output = $ty;
+
+                fn checked_op(self, rhs: $scalar, op: impl Fn($scalar, $scalar) -> Option<$scalar>) -> Option<$ty> {
+                    self.checked_op($ty::splat(rhs), op)
+                }
+            }
+
+            impl CheckedArith<$ty> for $scalar {
+                type Scalar = $scalar;
+                type Output = $ty;
+
+                fn checked_op(self, rhs: $ty, op: impl Fn($scalar, $scalar) -> Option<$scalar>) -> Option<$ty> {
+                    $ty::splat(self).checked_op(rhs, op)
+                }
+            }
+
+            impl CheckedDot for $ty {
+                type Scalar = $scalar;
+
+                fn checked_dot(self, rhs: $ty) -> Option<$scalar> {
+                    let mut dot: $scalar = 0;
+                    $(dot = dot.checked_add(self.$field.checked_mul(rhs.$field)?)?;)*
+                    Some(dot)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_checked_signed {
+    ($($ty:ident($scalar:ty)),* $(,)?) => {
+        $(
+            impl CheckedSigned for $ty {
+                fn checked_neg(self) -> Option<$ty> {
+                    $ty::ZERO.checked_op(self, <$scalar>::checked_sub)
+                }
+
+                fn checked_abs(self) -> Option<$ty> {
+                    self.checked_op(self, |value, _| value.checked_abs())
+                }
+            }
+        )*
+    };
+}
+
+impl_checked_arith! {
+    IVec2(i32) { x, y },
+    IVec3(i32) { x, y, z },
+    IVec4(i32) { x, y, z, w },
+    UVec2(u32) { x, y },
+    UVec3(u32) { x, y, z },
+    UVec4(u32) { x, y, z, w },
+}
+
+impl_checked_signed! {
+    IVec2(i32),
+    IVec3(i32),
+    IVec4(i32),
+}
+
+impl_lua_newtype! {
+    IVec2
+    fields { x: i32, y: i32 }
+    consts { ZERO, ONE, X, Y }
+    constructors { new(x: i32, y: i32), splat(v: i32) }
+    methods {
+        min(rhs: IVec2),
+        max(rhs: IVec2),
+        clamp(min: IVec2, max: IVec2),
+        as_vec2(),
+    }
+    checked_methods { dot(rhs: IVec2), abs() }
+    checked_meta { Unm: neg }
+    checked_ops {
+        Add { (IVec2, IVec2), (IVec2, i32), (i32, IVec2) },
+        Sub { (IVec2, IVec2), (IVec2, i32), (i32, IVec2) },
+        Mul { (IVec2, IVec2), (IVec2, i32), (i32, IVec2) },
+        Div { (IVec2, IVec2), (IVec2, i32), (i32, IVec2) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    IVec3
+    fields { x: i32, y: i32, z: i32 }
+    consts { ZERO, ONE, X, Y, Z }
+    constructors { new(x: i32, y: i32, z: i32), splat(v: i32) }
+    methods {
+        min(rhs: IVec3),
+        max(rhs: IVec3),
+        clamp(min: IVec3, max: IVec3),
+        as_vec3(),
+    }
+    checked_methods { dot(rhs: IVec3), abs() }
+    checked_meta { Unm: neg }
+    checked_ops {
+        Add { (IVec3, IVec3), (IVec3, i32), (i32, IVec3) },
+        Sub { (IVec3, IVec3), (IVec3, i32), (i32, IVec3) },
+        Mul { (IVec3, IVec3), (IVec3, i32), (i32, IVec3) },
+        Div { (IVec3, IVec3), (IVec3, i32), (i32, IVec3) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    IVec4
+    fields { x: i32, y: i32, z: i32, w: i32 }
+    consts { ZERO, ONE, X, Y, Z, W }
+    constructors { new(x: i32, y: i32, z: i32, w: i32), splat(v: i32) }
+    methods {
+        min(rhs: IVec4),
+        max(rhs: IVec4),
+        clamp(min: IVec4, max: IVec4),
+        as_vec4(),
+    }
+    checked_methods { dot(rhs: IVec4), abs() }
+    checked_meta { Unm: neg }
+    checked_ops {
+        Add { (IVec4, IVec4), (IVec4, i32), (i32, IVec4) },
+        Sub { (IVec4, IVec4), (IVec4, i32), (i32, IVec4) },
+        Mul { (IVec4, IVec4), (IVec4, i32), (i32, IVec4) },
+        Div { (IVec4, IVec4), (IVec4, i32), (i32, IVec4) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    UVec2
+    fields { x: u32, y: u32 }
+    consts { ZERO, ONE, X, Y }
+    constructors { new(x: u32, y: u32), splat(v: u32) }
+    methods {
+        min(rhs: UVec2),
+        max(rhs: UVec2),
+        clamp(min: UVec2, max: UVec2),
+        as_vec2(),
+    }
+    checked_methods { dot(rhs: UVec2) }
+    checked_ops {
+        Add { (UVec2, UVec2), (UVec2, u32), (u32, UVec2) },
+        Sub { (UVec2, UVec2), (UVec2, u32), (u32, UVec2) },
+        Mul { (UVec2, UVec2), (UVec2, u32), (u32, UVec2) },
+        Div { (UVec2, UVec2), (UVec2, u32), (u32, UVec2) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    UVec3
+    fields { x: u32, y: u32, z: u32 }
+    consts { ZERO, ONE, X, Y, Z }
+    constructors { new(x: u32, y: u32, z: u32), splat(v: u32) }
+    methods {
+        min(rhs: UVec3),
+        max(rhs: UVec3),
+        clamp(min: UVec3, max: UVec3),
+        as_vec3(),
+    }
+    checked_methods { dot(rhs: UVec3) }
+    checked_ops {
+        Add { (UVec3, UVec3), (UVec3, u32), (u32, UVec3) },
+        Sub { (UVec3, UVec3), (UVec3, u32), (u32, UVec3) },
+        Mul { (UVec3, UVec3), (UVec3, u32), (u32, UVec3) },
+        Div { (UVec3, UVec3), (UVec3, u32), (u32, UVec3) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    UVec4
+    fields { x: u32, y: u32, z: u32, w: u32 }
+    consts { ZERO, ONE, X, Y, Z, W }
+    constructors { new(x: u32, y: u32, z: u32, w: u32), splat(v: u32) }
+    methods {
+        min(rhs: UVec4),
+        max(rhs: UVec4),
+        clamp(min: UVec4, max: UVec4),
+        as_vec4(),
+    }
+    checked_methods { dot(rhs: UVec4) }
+    checked_ops {
+        Add { (UVec4, UVec4), (UVec4, u32), (u32, UVec4) },
+        Sub { (UVec4, UVec4), (UVec4, u32), (u32, UVec4) },
+        Mul { (UVec4, UVec4), (UVec4, u32), (u32, UVec4) },
+        Div { (UVec4, UVec4), (UVec4, u32), (u32, UVec4) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    Quat
+    fields { x: f32, y: f32, z: f32, w: f32 }
+    consts { IDENTITY }
+    constructors {
+        from_xyzw(x: f32, y: f32, z: f32, w: f32),
+        from_axis_angle(axis: Vec3, angle: f32),
+        from_scaled_axis(v: Vec3),
+        from_rotation_x(angle: f32),
+        from_rotation_y(angle: f32),
+        from_rotation_z(angle: f32),
+        from_rotation_arc(from: Vec3, to: Vec3),
+    }
+    methods {
+        dot(rhs: Quat),
+        length(),
+        length_squared(),
+        normalize(),
+        inverse(),
+        conjugate(),
+        is_normalized(),
+        angle_between(rhs: Quat),
+        lerp(end: Quat, s: f32),
+        slerp(end: Quat, s: f32),
+        mul_vec3(v: Vec3),
+    }
+    meta { Unm: neg, Len: length }
+    ops {
+        Add { (Quat, Quat) },
+        Sub { (Quat, Quat) },
+        Mul { (Quat, Quat), (Quat, Vec3), (Quat, f32) },
+        Div { (Quat, f32) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    Mat3
+    fields { x_axis: Vec3, y_axis: Vec3, z_axis: Vec3 }
+    consts { ZERO, IDENTITY }
+    constructors {
+        from_cols(x_axis: Vec3, y_axis: Vec3, z_axis: Vec3),
+        from_diagonal(diagonal: Vec3),
+        from_quat(rotation: Quat),
+        from_axis_angle(axis: Vec3, angle: f32),
+        from_rotation_x(angle: f32),
+        from_rotation_y(angle: f32),
+        from_rotation_z(angle: f32),
+    }
+    methods {
+        transpose(),
+        inverse(),
+        determinant(),
+        mul_vec3(v: Vec3),
+    }
+    ops {
+        Add { (Mat3, Mat3) },
+        Sub { (Mat3, Mat3) },
+        Mul { (Mat3, Mat3), (Mat3, Vec3), (Mat3, f32) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    Mat4
+    fields { x_axis: Vec4, y_axis: Vec4, z_axis: Vec4, w_axis: Vec4 }
+    consts { ZERO, IDENTITY }
+    constructors {
+        from_cols(x_axis: Vec4, y_axis: Vec4, z_axis: Vec4, w_axis: Vec4),
+        from_diagonal(diagonal: Vec4),
+        from_quat(rotation: Quat),
+        from_translation(translation: Vec3),
+        from_scale(scale: Vec3),
+        from_rotation_translation(rotation: Quat, translation: Vec3),
+        from_scale_rotation_translation(scale: Vec3, rotation: Quat, translation: Vec3),
+        from_axis_angle(axis: Vec3, angle: f32),
+        from_rotation_x(angle: f32),
+        from_rotation_y(angle: f32),
+        from_rotation_z(angle: f32),
+    }
+    methods {
+        transpose(),
+        inverse(),
+        determinant(),
+        mul_vec4(v: Vec4),
+        transform_point3(v: Vec3),
+        transform_vector3(v: Vec3),
+    }
+    ops {
+        Add { (Mat4, Mat4) },
+        Sub { (Mat4, Mat4) },
+        Mul { (Mat4, Mat4), (Mat4, Vec4), (Mat4, f32) },
+    }
+    Debug(original)
+}
+
+impl_lua_newtype! {
+    Color
+    fields { r: f32 = r / set_r, g: f32 = g / set_g, b: f32 = b / set_b, a: f32 = a / set_a }
+    consts { WHITE, BLACK, RED, GREEN, BLUE, YELLOW, NONE }
+    constructors {
+        rgb(r: f32, g: f32, b: f32),
+        rgba(r: f32, g: f32, b: f32, a: f32),
+        rgb_linear(r: f32, g: f32, b: f32),
+        rgba_linear(r: f32, g: f32, b: f32, a: f32),
+        hsl(hue: f32, saturation: f32, lightness: f32),
+        hsla(hue: f32, saturation: f32, lightness: f32, alpha: f32),
+    }
+    methods {
+        as_rgba(),
+        as_rgba_linear(),
+        as_hsla(),
+    }
+    ops {
+        Add { (Color, Color) },
+        Mul { (Color, f32) },
+    }
+    Debug(original)
+}
+
+macro_rules! impl_math_reflect {
+    ($($ty:ident),* $(,)?) => {
+        paste::paste! {
+            /// Exposes the global constructor table of every math type.
+            pub(crate) fn register(lua: &Lua) -> Result<()> {
+                $([<Lua $ty>]::register(lua)?;)*
+
+                Ok(())
+            }
+
+            fn register_math_converters(registry: &mut LuaConverterRegistry) {
+                $(registry.register_proxy::<$ty>();)*
+            }
+        }
+    };
+}
+
+impl_math_reflect!(Vec2, Vec3, Vec4, IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Quat, Mat3, Mat4, Color);
+
+/// Converts the math types to and from their userdata.
+pub(crate) fn register_converters(registry: &mut LuaConverterRegistry) {
+    register_math_converters(registry);
+    registry.register_with::<Vec3>(proxy_to_lua::<Vec3>, vec3_from_lua);
+}
+
+/// Also accepts `{ x = 1, y = 2, z = 3 }` tables.
+fn vec3_from_lua<'lua>(lua: &'lua Lua, value: Value<'lua>) -> Result<Box<dyn Reflect>> {
+    match value {
+        Value::Table(table) => Ok(Box::new(Vec3::new(table.get("x")?, table.get("y")?, table.get("z")?))),
+        value => proxy_from_lua::<Vec3>(lua, value),
+    }
+}
@@ -0,0 +1,57 @@
+use bevy::reflect::*;
+use bevy::utils::HashMap;
+use mlua::prelude::*;
+use mlua::FromLuaMulti;
+use mlua::ToLuaMulti;
+
+use std::any::TypeId;
+use std::sync::Arc;
+
+/// A Rust method called on the reflected value a reference points to.
+pub(crate) type LuaMethod = Arc<dyn for<'lua> Fn(&'lua Lua, &mut dyn Reflect, LuaMultiValue<'lua>) -> LuaResult<LuaMultiValue<'lua>> + Send + Sync>;
+
+/// Rust methods callable on references to reflected values, by the `TypeId` of the value and name.
+///
+/// Add methods with [`LuaAppExt::add_lua_method`](crate::LuaAppExt::add_lua_method).
+#[derive(Clone, Default)]
+pub struct LuaMethodRegistry {
+    methods: HashMap<TypeId, HashMap<String, LuaMethod>>,
+}
+
+impl LuaMethodRegistry {
+    /// Makes `method` callable as `name` on references to values of `T`, e.g.
+    /// `entity:get("Health"):damage(10)`. Arguments are converted with `FromLuaMulti`, and
+    /// references to values with a Lua equivalent are passed as that value.
+    pub fn add<T, A, R, F>(&mut self, name: &str, method: F)
+    where
+        T: Reflect,
+        A: for<'lua> FromLuaMulti<'lua>,
+        R: for<'lua> ToLuaMulti<'lua>,
+        F: Fn(&mut T, A) -> R + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+
+        let erased = erase(move |lua, value, args| {
+            let value =
+                value.downcast_mut::<T>()
+                .ok_or_else(|| LuaError::RuntimeError(format!("Expected a value of type {}, got {}", std::any::type_name::<T>(), value.type_name())))?;
+
+            method(value, A::from_lua_multi(args, lua)?).to_lua_multi(lua)
+        });
+
+        self.methods.entry(TypeId::of::<T>()).or_default().insert(name, erased);
+    }
+
+    /// The method `name` of values of the type of `value`, if one is registered.
+    pub(crate) fn get(&self, value: &dyn Reflect, name: &str) -> Option<LuaMethod> {
+        self.methods.get(&value.any().type_id())?.get(name).cloned()
+    }
+}
+
+/// Lets the closure's signature be inferred as generic over the Lua lifetime.
+fn erase<F>(method: F) -> LuaMethod
+where
+    F: for<'lua> Fn(&'lua Lua, &mut dyn Reflect, LuaMultiValue<'lua>) -> LuaResult<LuaMultiValue<'lua>> + Send + Sync + 'static,
+{
+    Arc::new(method)
+}
@@ -0,0 +1,159 @@
+use bevy::reflect::*;
+
+use std::sync::Arc;
+
+/// One step from a reflected value into one of its parts.
+#[derive(Clone)]
+pub(crate) enum PathSegment {
+    /// A named struct field.
+    Field(String),
+    /// A 0-based tuple, tuple struct or list index.
+    Index(usize),
+    /// A map key, already converted to the map's key type, and how to display it.
+    Key(Arc<dyn Reflect>, String),
+}
+
+impl std::fmt::Debug for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Field(name) => f.write_fmt(format_args!(".{}", name)),
+            // Shown 1-based, as scripts index
+            PathSegment::Index(index) => f.write_fmt(format_args!("[{}]", index + 1)),
+            PathSegment::Key(_, label) => f.write_fmt(format_args!("[{}]", label)),
+        }
+    }
+}
+
+/// Follows `path` from `value`, or returns `None` if it leads nowhere.
+pub(crate) fn walk<'a>(mut value: &'a dyn Reflect, path: &[PathSegment]) -> Option<&'a dyn Reflect> {
+    for segment in path {
+        value = match (segment, value.reflect_ref()) {
+            (PathSegment::Field(name), ReflectRef::Struct(value)) => value.field(name)?,
+            (PathSegment::Index(index), ReflectRef::TupleStruct(value)) => value.field(*index)?,
+            (PathSegment::Index(index), ReflectRef::Tuple(value)) => value.field(*index)?,
+            (PathSegment::Index(index), ReflectRef::List(value)) => value.get(*index)?,
+            (PathSegment::Key(key, _), ReflectRef::Map(value)) => value.get(&**key)?,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
+/// Follows `path` from `value` mutably, or returns `None` if it leads nowhere.
+pub(crate) fn walk_mut<'a>(mut value: &'a mut dyn Reflect, path: &[PathSegment]) -> Option<&'a mut dyn Reflect> {
+    for segment in path {
+        value = match (segment, value.reflect_mut()) {
+            (PathSegment::Field(name), ReflectMut::Struct(value)) => value.field_mut(name)?,
+            (PathSegment::Index(index), ReflectMut::TupleStruct(value)) => value.field_mut(*index)?,
+            (PathSegment::Index(index), ReflectMut::Tuple(value)) => value.field_mut(*index)?,
+            (PathSegment::Index(index), ReflectMut::List(value)) => value.get_mut(*index)?,
+            (PathSegment::Key(key, _), ReflectMut::Map(value)) => value.get_mut(&**key)?,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
+/// The type name of the elements of `list` if it is a `Vec`, read from the list's own type name.
+pub(crate) fn element_type_name(list: &dyn List) -> Option<&str> {
+    list.type_name().strip_prefix("alloc::vec::Vec<")?.strip_suffix('>')
+}
+
+/// Whether `element` is known to be of the element type of `list`, which reflection panics on
+/// pushing anything else. The type is taken from the first element, or from the type name of an
+/// empty `Vec`; an element of a list whose type can't be told doesn't fit.
+pub(crate) fn fits_list(list: &dyn List, element: &dyn Reflect) -> bool {
+    match list.get(0) {
+        Some(first) => first.any().type_id() == element.any().type_id(),
+        None => element_type_name(list).map_or(false, |name| name == element.type_name()),
+    }
+}
+
+/// Whether applying `rvalue` to `lvalue` goes through without reflection panicking: shapes must
+/// match, values must be of the same type and elements a list grows by of its element type.
+pub(crate) fn fits(lvalue: &dyn Reflect, rvalue: &dyn Reflect) -> bool {
+    match (lvalue.reflect_ref(), rvalue.reflect_ref()) {
+        (ReflectRef::Struct(lvalue), ReflectRef::Struct(rvalue)) =>
+            rvalue.iter_fields().enumerate().all(|(index, field)| {
+                match rvalue.name_at(index).and_then(|name| lvalue.field(name)) {
+                    Some(lfield) => fits(lfield, field),
+                    None => true,
+                }
+            }),
+        (ReflectRef::TupleStruct(lvalue), ReflectRef::TupleStruct(rvalue)) =>
+            rvalue.iter_fields().enumerate().all(|(index, field)| lvalue.field(index).map_or(true, |lfield| fits(lfield, field))),
+        (ReflectRef::Tuple(lvalue), ReflectRef::Tuple(rvalue)) =>
+            rvalue.iter_fields().enumerate().all(|(index, field)| lvalue.field(index).map_or(true, |lfield| fits(lfield, field))),
+        (ReflectRef::List(lvalue), ReflectRef::List(rvalue)) =>
+            rvalue.iter().enumerate().all(|(index, element)| match lvalue.get(index) {
+                Some(lelement) => fits(lelement, element),
+                // Grows the list by a clone, which stays of the element type only for values
+                None => fits_list(lvalue, &*element.clone_value()),
+            }),
+        (ReflectRef::Map(lvalue), ReflectRef::Map(rvalue)) =>
+            rvalue.iter().all(|(key, value)| lvalue.get(key).map_or(true, |lvalue| fits(lvalue, value))),
+        (ReflectRef::Value(_), ReflectRef::Value(_)) => lvalue.any().type_id() == rvalue.any().type_id(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Reflect, Default)]
+    struct Health {
+        current: f32,
+        regen: Vec<f32>,
+    }
+
+    #[test]
+    fn fits_values_of_the_same_type() {
+        assert!(fits(&1.0f32, &2.0f32));
+        assert!(!fits(&1.0f32, &2.0f64));
+        assert!(!fits(&1.0f32, &DynamicStruct::default()));
+    }
+
+    #[test]
+    fn fits_struct_fields_by_type() {
+        let health = Health::default();
+
+        let mut patch = DynamicStruct::default();
+        patch.insert("current", 5.0f32);
+        assert!(fits(&health, &patch));
+
+        let mut patch = DynamicStruct::default();
+        patch.insert("current", 5i32);
+        assert!(!fits(&health, &patch));
+    }
+
+    #[test]
+    fn fits_list_elements_by_type() {
+        let health = Health::default();
+
+        let mut regen = DynamicList::default();
+        regen.push(1.0f32);
+        let mut patch = DynamicStruct::default();
+        patch.insert("regen", regen);
+        assert!(fits(&health, &patch));
+
+        let mut regen = DynamicList::default();
+        regen.push(1.0f64);
+        let mut patch = DynamicStruct::default();
+        patch.insert("regen", regen);
+        assert!(!fits(&health, &patch));
+    }
+
+    #[test]
+    fn fits_list_reads_the_element_type_of_empty_vecs() {
+        let empty: Vec<f32> = Vec::new();
+        assert!(fits_list(&empty, &1.0f32));
+        assert!(!fits_list(&empty, &1.0f64));
+
+        let full = vec![1u8];
+        assert!(fits_list(&full, &2u8));
+        assert!(!fits_list(&full, &2u16));
+    }
+}
@@ -0,0 +1,42 @@
+use bevy::ecs::system::Resource;
+use bevy::prelude::*;
+use bevy::reflect::FromType;
+
+/// The resource counterpart of `ReflectComponent`, letting scripts reach a resource by name.
+///
+/// Add `#[reflect(Resource)]` to a reflected resource and register it with `App::register_type`.
+#[derive(Clone)]
+pub struct ReflectResource {
+    reflect_resource: fn(&World) -> Option<&dyn Reflect>,
+    reflect_resource_unchecked_mut: unsafe fn(&World) -> Option<&mut dyn Reflect>,
+}
+
+impl ReflectResource {
+    pub fn reflect_resource<'w>(&self, world: &'w World) -> Option<&'w dyn Reflect> {
+        (self.reflect_resource)(world)
+    }
+
+    /// # Safety
+    /// Same as `World::get_resource_unchecked_mut`: nothing else may access the resource while
+    /// the returned reference is alive.
+    pub unsafe fn reflect_resource_unchecked_mut<'w>(&self, world: &'w World) -> Option<&'w mut dyn Reflect> {
+        (self.reflect_resource_unchecked_mut)(world)
+    }
+}
+
+impl<R: Reflect + Resource> FromType<R> for ReflectResource {
+    fn from_type() -> Self {
+        ReflectResource {
+            reflect_resource: reflect_resource::<R>,
+            reflect_resource_unchecked_mut: reflect_resource_unchecked_mut::<R>,
+        }
+    }
+}
+
+fn reflect_resource<R: Reflect + Resource>(world: &World) -> Option<&dyn Reflect> {
+    world.get_resource::<R>().map(|res| res as &dyn Reflect)
+}
+
+unsafe fn reflect_resource_unchecked_mut<R: Reflect + Resource>(world: &World) -> Option<&mut dyn Reflect> {
+    world.get_resource_unchecked_mut::<R>().map(|res| res.into_inner() as &mut dyn Reflect)
+}
@@ -0,0 +1,79 @@
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::utils::HashSet;
+use mlua::prelude::*;
+
+use crate::LuaScript;
+
+/// Sent whenever a script fails to compile or one of its callbacks or systems raises an error.
+#[derive(Debug, Clone)]
+pub struct LuaScriptError {
+    pub script: Handle<LuaScript>,
+    /// The entity the callback ran for, or `None` if the script failed to compile or failed as a
+    /// [`LuaSystem`](crate::LuaSystem).
+    pub entity: Option<Entity>,
+    pub message: String,
+    /// The Lua stack traceback (as printed by `debug.traceback`), empty if there was none.
+    pub traceback: String,
+}
+
+impl LuaScriptError {
+    pub(crate) fn new(script: Handle<LuaScript>, entity: Option<Entity>, error: &LuaError) -> Self {
+        let (message, traceback) = match error {
+            LuaError::CallbackError { traceback, cause } => (cause.to_string(), traceback.clone()),
+            // Errors raised by Lua code have the traceback appended to their message
+            LuaError::RuntimeError(message) => match message.split_once("\nstack traceback:") {
+                Some((message, traceback)) => (message.to_string(), format!("stack traceback:{}", traceback)),
+                None => (message.clone(), String::new()),
+            },
+            error => (error.to_string(), String::new()),
+        };
+
+        LuaScriptError { script, entity, message, traceback }
+    }
+}
+
+/// The scripts attached to an entity. Only entities with this component are run by the Lua host.
+#[derive(Component, Default, Clone)]
+pub struct LuaScripts(pub Vec<Handle<LuaScript>>);
+
+/// Tracks which scripts have had `on_spawn` called for which entities.
+#[derive(Default)]
+pub(crate) struct LuaScriptInstances(HashMap<Entity, Vec<HandleId>>);
+
+impl LuaScriptInstances {
+    /// Forgets every instance that is no longer in `scripted`, returning them so `on_despawn` can be called.
+    pub fn retain(&mut self, scripted: &[(Entity, Vec<HandleId>)]) -> Vec<(Entity, HandleId)> {
+        let live: HashSet<(Entity, HandleId)> =
+            scripted.iter()
+            .flat_map(|(entity, scripts)| scripts.iter().map(move |script| (*entity, *script)))
+            .collect();
+
+        let mut despawned = Vec::new();
+
+        self.0.retain(|entity, scripts| {
+            scripts.retain(|script| {
+                let alive = live.contains(&(*entity, *script));
+                if !alive {
+                    despawned.push((*entity, *script));
+                }
+                alive
+            });
+            !scripts.is_empty()
+        });
+
+        despawned
+    }
+
+    /// Records an instance, returning `true` if it is new and needs `on_spawn`.
+    pub fn spawn(&mut self, entity: Entity, script: HandleId) -> bool {
+        let scripts = self.0.entry(entity).or_default();
+        if scripts.contains(&script) {
+            false
+        } else {
+            scripts.push(script);
+            true
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use bevy::asset::HandleId;
+use bevy::ecs::archetype::Archetype;
+use bevy::ecs::archetype::ArchetypeComponentId;
+use bevy::ecs::archetype::ArchetypeId;
+use bevy::ecs::component::ComponentId;
+use bevy::ecs::query::Access;
+use bevy::ecs::system::System;
+use bevy::ecs::system::SystemId;
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistryArc;
+use bevy::utils::HashSet;
+use mlua::prelude::*;
+
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::registered_type;
+use crate::BevyLua;
+use crate::LuaConverterRegistry;
+use crate::LuaMethodRegistry;
+use crate::LuaScript;
+use crate::LuaScriptCache;
+use crate::LuaScriptError;
+use crate::LuaWorldRef;
+use crate::ReflectResource;
+
+/// The components and resources a Lua system may read and write.
+#[derive(Default)]
+pub(crate) struct LuaAccess {
+    reads: HashSet<TypeId>,
+    writes: HashSet<TypeId>,
+}
+
+impl LuaAccess {
+    pub fn permits(&self, type_id: TypeId, write: bool) -> bool {
+        self.writes.contains(&type_id) || (!write && self.reads.contains(&type_id))
+    }
+}
+
+/// A function of a Lua script run as a system, which the scheduler can run in parallel with other
+/// systems according to the components and resources it declares:
+///
+/// ```ignore
+/// app.add_system(LuaSystem::new("scripts/steering.lua", "steer").reads(["Transform"]).writes(["Velocity"]));
+/// ```
+///
+/// The script must return a table holding the function, which is called with a world handle
+/// limited to what was declared: touching anything else, or spawning, despawning, inserting or
+/// removing components, raises a Lua error. Declared types must be registered like any other
+/// type scripts use.
+pub struct LuaSystem {
+    path: String,
+    function: String,
+    reads: Vec<String>,
+    writes: Vec<String>,
+    id: SystemId,
+    name: Cow<'static, str>,
+    script: Option<Handle<LuaScript>>,
+    world_ref: LuaWorldRef,
+    components: Vec<(ComponentId, bool)>,
+    /// Declared components that had no id yet, as none was ever inserted.
+    pending: Vec<(TypeId, bool)>,
+    resource_archetype: Option<ArchetypeId>,
+    component_access: Access<ComponentId>,
+    archetype_component_access: Access<ArchetypeComponentId>,
+}
+
+impl LuaSystem {
+    /// Runs `function` of the script at the asset path `path`.
+    pub fn new(path: &str, function: &str) -> Self {
+        LuaSystem {
+            path: path.to_string(),
+            function: function.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            id: SystemId::new(),
+            name: Cow::Owned(format!("{}::{}", path, function)),
+            script: None,
+            world_ref: LuaWorldRef::default(),
+            components: Vec::new(),
+            pending: Vec::new(),
+            resource_archetype: None,
+            component_access: Access::default(),
+            archetype_component_access: Access::default(),
+        }
+    }
+
+    /// Declares components and resources the system reads, by name.
+    pub fn reads<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        self.reads.extend(names.into_iter().map(str::to_string));
+        self
+    }
+
+    /// Declares components and resources the system writes, by name.
+    pub fn writes<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Self {
+        self.writes.extend(names.into_iter().map(str::to_string));
+        self
+    }
+
+    /// Declares one component or resource, returning its `TypeId` if scripts may use it.
+    fn declare(&mut self, world: &World, name: &str, write: bool) -> Option<TypeId> {
+        if let Some((_, type_id, _)) = registered_type::<ReflectComponent>(world, name) {
+            // Components only get an id once first inserted, which `run_unsafe` looks out for
+            match world.components().get_id(type_id) {
+                Some(component_id) => self.declare_component(component_id, write),
+                None => self.pending.push((type_id, write)),
+            }
+
+            return Some(type_id);
+        }
+
+        match registered_type::<ReflectResource>(world, name) {
+            Some((_, type_id, _)) if self.declare_resource(world, type_id, write) => Some(type_id),
+            Some(_) => {
+                warn!("Resource {} used by Lua system {} isn't in the world, so the system can't access it", name, self.name);
+                None
+            },
+            None => panic!("Lua system {} uses {}, which is not a registered component or resource", self.name, name),
+        }
+    }
+
+    fn declare_component(&mut self, component_id: ComponentId, write: bool) {
+        self.components.push((component_id, write));
+
+        match write {
+            true => self.component_access.add_write(component_id),
+            false => self.component_access.add_read(component_id),
+        }
+    }
+
+    /// Declares the pending components that have since been given an id. Archetypes created from
+    /// then on are matched against them instead of being taken as accessed as a whole.
+    fn resolve_pending(&mut self, world: &World) {
+        let mut pending = std::mem::take(&mut self.pending);
+
+        pending.retain(|(type_id, write)| match world.components().get_id(*type_id) {
+            Some(component_id) => {
+                self.declare_component(component_id, *write);
+                false
+            },
+            None => true,
+        });
+
+        self.pending = pending;
+    }
+
+    /// Adds a resource to the access, returning `false` if it isn't in the world.
+    fn declare_resource(&mut self, world: &World, type_id: TypeId, write: bool) -> bool {
+        let component_id = match world.components().get_resource_id(type_id) {
+            Some(component_id) => component_id,
+            None => return false,
+        };
+
+        let archetype_component_id = match world.archetypes().resource().get_archetype_component_id(component_id) {
+            Some(archetype_component_id) => archetype_component_id,
+            None => return false,
+        };
+
+        match write {
+            true => {
+                self.component_access.add_write(component_id);
+                self.archetype_component_access.add_write(archetype_component_id);
+            },
+            false => {
+                self.component_access.add_read(component_id);
+                self.archetype_component_access.add_read(archetype_component_id);
+            },
+        }
+
+        true
+    }
+}
+
+impl System for LuaSystem {
+    type In = ();
+    type Out = ();
+
+    fn name(&self) -> Cow<'static, str> {
+        self.name.clone()
+    }
+
+    fn id(&self) -> SystemId {
+        self.id
+    }
+
+    fn new_archetype(&mut self, archetype: &Archetype) {
+        for (component_id, write) in &self.components {
+            if let Some(id) = archetype.get_archetype_component_id(*component_id) {
+                match write {
+                    true => self.archetype_component_access.add_write(id),
+                    false => self.archetype_component_access.add_read(id),
+                }
+            }
+        }
+
+        // A pending component may be any of the archetype's, so all of them count as accessed
+        if !self.pending.is_empty() && Some(archetype.id()) != self.resource_archetype {
+            let write = self.pending.iter().any(|(_, write)| *write);
+
+            for component_id in archetype.components() {
+                if let Some(id) = archetype.get_archetype_component_id(component_id) {
+                    match write {
+                        true => self.archetype_component_access.add_write(id),
+                        false => self.archetype_component_access.add_read(id),
+                    }
+                }
+            }
+        }
+    }
+
+    fn component_access(&self) -> &Access<ComponentId> {
+        &self.component_access
+    }
+
+    fn archetype_component_access(&self) -> &Access<ArchetypeComponentId> {
+        &self.archetype_component_access
+    }
+
+    fn is_send(&self) -> bool {
+        true
+    }
+
+    unsafe fn run_unsafe(&mut self, _input: (), world: &World) {
+        if !self.pending.is_empty() {
+            self.resolve_pending(world);
+        }
+
+        let script = match &self.script {
+            Some(script) => script.id,
+            None => return,
+        };
+
+        let lua = world.get_resource::<BevyLua>().expect("LuaPlugin must be added before Lua systems run");
+        let cache = world.get_resource::<LuaScriptCache>().expect("LuaPlugin must be added before Lua systems run");
+        let lua = lua.lock().expect("Failed to lock Lua mutex");
+
+        // SAFETY: The world reference only permits the access `initialize` declared to the scheduler
+        let result = self.world_ref.scope_shared(world, || run_function(&lua, cache, script, &self.function, &self.world_ref));
+
+        if let Err(err) = result {
+            error!("Lua system {} failed: {}", self.name, err);
+
+            // SAFETY: The events are declared as written in `initialize`
+            if let Some(mut errors) = world.get_resource_unchecked_mut::<Events<LuaScriptError>>() {
+                errors.send(LuaScriptError::new(Handle::weak(script), None, &err));
+            }
+        }
+    }
+
+    fn apply_buffers(&mut self, _world: &mut World) {}
+
+    fn initialize(&mut self, world: &mut World) {
+        let asset_server = world.get_resource::<AssetServer>().expect("Lua systems need the AssetServer to load their script");
+        self.script = Some(asset_server.load(self.path.as_str()));
+
+        self.resource_archetype = Some(world.archetypes().resource().id());
+
+        let mut access = LuaAccess::default();
+
+        // Writes are declared first so a name both read and written is written
+        for (names, write) in [(self.writes.clone(), true), (self.reads.clone(), false)] {
+            for name in names {
+                if !write && self.writes.contains(&name) {
+                    continue;
+                }
+
+                if let Some(type_id) = self.declare(world, &name, write) {
+                    match write {
+                        true => access.writes.insert(type_id),
+                        false => access.reads.insert(type_id),
+                    };
+                }
+            }
+        }
+
+        // What running a script needs regardless of what it touches
+        for type_id in [
+            TypeId::of::<BevyLua>(),
+            TypeId::of::<LuaScriptCache>(),
+            TypeId::of::<TypeRegistryArc>(),
+            TypeId::of::<LuaConverterRegistry>(),
+            TypeId::of::<LuaMethodRegistry>(),
+        ] {
+            self.declare_resource(world, type_id, false);
+        }
+        self.declare_resource(world, TypeId::of::<Events<LuaScriptError>>(), true);
+
+        self.world_ref = LuaWorldRef {
+            access: Some(Arc::new(access)),
+            ..LuaWorldRef::default()
+        };
+    }
+
+    fn check_change_tick(&mut self, _change_tick: u32) {}
+}
+
+fn run_function(lua: &Lua, cache: &LuaScriptCache, script: HandleId, name: &str, world: &LuaWorldRef) -> LuaResult<()> {
+    // Nothing runs until the script has finished loading and compiling
+    let module = match cache.get(lua, script)? {
+        Some(module) => module,
+        None => return Ok(()),
+    };
+
+    let function: LuaFunction =
+        module.get::<_, Option<LuaFunction>>(name)?
+        .ok_or_else(|| LuaError::RuntimeError(format!("The script doesn't return a function named {}", name)))?;
+
+    function.call(world.clone())
+}
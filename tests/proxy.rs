@@ -0,0 +1,98 @@
+use wasm_hosting_tests::lua_methods;
+use wasm_hosting_tests::mlua::Lua;
+use wasm_hosting_tests::LuaNewtype;
+use wasm_hosting_tests::LuaProxy;
+
+#[derive(Clone, Debug, LuaProxy)]
+#[lua(methods)]
+struct Counter {
+    count: i32,
+    step: f32,
+    #[lua(skip)]
+    label: String,
+}
+
+#[lua_methods]
+impl Counter {
+    #[lua(method)]
+    fn bump(&mut self, times: i32) {
+        self.count += times;
+    }
+
+    #[lua(method)]
+    fn doubled(&self) -> i32 {
+        self.count * 2
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+}
+
+/// A Lua state holding a `Counter` in the global `counter`.
+fn lua() -> Lua {
+    let lua = Lua::new();
+
+    let counter = Counter { count: 1, step: 0.5, label: "hidden".to_string() };
+    lua.globals().set("counter", counter.wrap()).unwrap();
+
+    lua
+}
+
+fn counter(lua: &Lua) -> Counter {
+    <Counter as LuaNewtype>::unwrap(lua.globals().get("counter").unwrap())
+}
+
+#[test]
+fn fields_round_trip() {
+    let lua = lua();
+
+    lua.load("counter.count = counter.count + 2; counter.step = counter.step * 3").exec().unwrap();
+
+    let counter = counter(&lua);
+    assert_eq!(counter.count, 3);
+    assert_eq!(counter.step, 1.5);
+    assert_eq!(counter.label, "hidden");
+}
+
+#[test]
+fn integer_fields_keep_their_range() {
+    let lua = lua();
+
+    assert_eq!(lua.load("return counter.count").eval::<i64>().unwrap(), 1);
+
+    lua.load("counter.count = -2147483648").exec().unwrap();
+    assert_eq!(counter(&lua).count, i32::MIN);
+
+    assert!(lua.load("counter.count = 2147483648").exec().is_err());
+    assert_eq!(counter(&lua).count, i32::MIN);
+}
+
+#[test]
+fn skipped_fields_are_hidden() {
+    let lua = lua();
+
+    let hidden: bool = lua.load(r#"
+        local ok, label = pcall(function() return counter.label end)
+        return not ok or label == nil
+    "#).eval().unwrap();
+    assert!(hidden);
+
+    assert!(lua.load(r#"counter.label = "shown""#).exec().is_err());
+    assert_eq!(counter(&lua).label, "hidden");
+}
+
+#[test]
+fn methods_are_exported() {
+    let lua = lua();
+
+    assert_eq!(lua.load("counter:bump(4); return counter:doubled()").eval::<i32>().unwrap(), 10);
+    assert_eq!(counter(&lua).count, 5);
+
+    // Only functions marked #[lua(method)] are exported, the others stay plain Rust methods
+    assert!(lua.load("counter:reset()").exec().is_err());
+
+    let mut counter = counter(&lua);
+    counter.reset();
+    assert_eq!(counter.count, 0);
+}